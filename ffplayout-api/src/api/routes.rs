@@ -17,8 +17,7 @@ use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, SaltString},
     Argon2, PasswordHasher, PasswordVerifier,
 };
-use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, TimeZone, Utc};
-use regex::Regex;
+use chrono::{Datelike, NaiveDateTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 use simplelog::*;
 use sqlx::{Pool, Sqlite};
@@ -31,6 +30,7 @@ use crate::db::{
 use crate::utils::{
     channels::{create_channel, delete_channel},
     control::{control_service, control_state, media_info, send_message, Process},
+    epg::read_program,
     errors::ServiceError,
     files::{
         browser, create_directory, remove_file_or_folder, rename_file, upload, MoveObject,
@@ -40,11 +40,11 @@ use crate::utils::{
     playlist::{delete_playlist, generate_playlist, read_playlist, write_playlist},
     playout_config, read_log_file, read_playout_config, Role,
 };
-use ffplayout_lib::{
-    utils::{
-        get_date_range, import::import_file, sec_to_time, time_to_sec, JsonPlaylist, PlayoutConfig,
-    },
-    vec_strings,
+use ffplayout_lib::utils::{
+    epg::{eit, xmltv},
+    export::{export_playlist, ExportFormat},
+    import::{import_file, ImportFormat},
+    JsonPlaylist, PlayoutConfig,
 };
 
 #[derive(Serialize)]
@@ -66,6 +66,14 @@ pub struct DateObj {
     date: String,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ExportObj {
+    #[serde(default)]
+    date: String,
+    #[serde(default)]
+    format: String,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 struct FileObj {
     #[serde(default)]
@@ -78,6 +86,8 @@ pub struct ImportObj {
     file: String,
     #[serde(default)]
     date: String,
+    #[serde(default)]
+    format: String,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -106,16 +116,6 @@ fn time_before() -> NaiveDateTime {
         .naive_local()
 }
 
-#[derive(Debug, Serialize)]
-struct ProgramItem {
-    source: String,
-    start: String,
-    r#in: f64,
-    out: f64,
-    duration: f64,
-    category: String,
-}
-
 /// #### User Handling
 ///
 /// **Login**
@@ -577,6 +577,11 @@ pub async fn send_text_message(
 /// - next
 /// - back
 /// - reset
+/// - jump, with `"index": 12` to play this clip from playlist
+/// - seek_time, with `"time": "14:30:00"` to play the playlist from this time
+/// - seek, with `"position": 120.5` to seek in current clip
+/// - hold, to show the slate
+/// - release, with `"mode": "resume"` to go on from where it was hold, default is in sync
 ///
 /// ```BASH
 /// curl -X POST http://127.0.0.1:8787/api/control/1/playout/ -H 'Content-Type: application/json'
@@ -589,7 +594,7 @@ pub async fn control_playout(
     id: web::Path<i32>,
     control: web::Json<Process>,
 ) -> Result<impl Responder, ServiceError> {
-    match control_state(&pool.into_inner(), *id, control.command.clone()).await {
+    match control_state(&pool.into_inner(), *id, control.into_inner()).await {
         Ok(res) => Ok(res.text().await.unwrap_or_else(|_| "Success".into())),
         Err(e) => Err(e),
     }
@@ -716,6 +721,50 @@ pub async fn get_playlist(
     }
 }
 
+/// **Export playlist**
+///
+/// Export playlist with computed start times to m3u, csv or edl (CMX3600). Default format is m3u.
+///
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/api/playlist/1/export?date=2022-06-20&format=csv
+/// -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[get("/playlist/{id}/export")]
+#[has_any_role("Role::Admin", "Role::User", type = "Role")]
+pub async fn export_playlist_file(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    obj: web::Query<ExportObj>,
+) -> Result<HttpResponse, ServiceError> {
+    let format = match obj.format.is_empty() {
+        true => ExportFormat::M3u,
+        false => obj
+            .format
+            .parse::<ExportFormat>()
+            .map_err(ServiceError::BadRequest)?,
+    };
+    let (config, _) = playout_config(&pool.clone().into_inner(), &id).await?;
+    let playlist = read_playlist(&pool.into_inner(), *id, obj.date.clone()).await?;
+    let extension = obj.format.trim();
+    let filename = format!(
+        "{}.{}",
+        playlist.date,
+        if extension.is_empty() {
+            "m3u"
+        } else {
+            extension
+        }
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{filename}\""),
+        ))
+        .body(export_playlist(&config, &playlist, format)))
+}
+
 /// **Save playlist**
 ///
 /// ```BASH
@@ -886,12 +935,19 @@ async fn save_file(
 
 /// **Import playlist**
 ///
-/// Import text/m3u file and convert it to a playlist
-/// lines with leading "#" will be ignore
+/// Import text/m3u/csv/xspf/edl file and convert it to a playlist.
+/// The format is taken from the file extension, or from the optional `format` parameter.
+/// In plain text files, lines with leading "#" will be ignore.
+///
+/// ```BASH
+/// curl -X PUT http://127.0.0.1:8787/api/file/1/import/?file=list.m3u&date=2022-06-20 \
+/// -H 'Authorization: Bearer <TOKEN>' -F "file=@list.m3u"
+/// ```
 ///
+/// * import a csv file, with explicit format
 /// ```BASH
-/// curl -X PUT http://127.0.0.1:8787/api/file/1/import/ -H 'Authorization: Bearer <TOKEN>'
-/// -F "file=@list.m3u"
+/// curl -X PUT http://127.0.0.1:8787/api/file/1/import/?file=list.txt&date=2022-06-20&format=csv \
+/// -H 'Authorization: Bearer <TOKEN>' -F "file=@list.txt"
 /// ```
 #[put("/file/{id}/import/")]
 #[has_any_role("Role::Admin", "Role::User", type = "Role")]
@@ -903,11 +959,19 @@ async fn import_playlist(
 ) -> Result<HttpResponse, ServiceError> {
     let file = Path::new(&obj.file).file_name().unwrap_or_default();
     let path = env::temp_dir().join(file).to_string_lossy().to_string();
+    let format = match obj.format.is_empty() {
+        true => None,
+        false => Some(
+            obj.format
+                .parse::<ImportFormat>()
+                .map_err(ServiceError::BadRequest)?,
+        ),
+    };
     let (config, _) = playout_config(&pool.clone().into_inner(), &id).await?;
     let channel = handles::select_channel(&pool.clone().into_inner(), &id).await?;
 
     upload(&pool.into_inner(), *id, payload, &path, true).await?;
-    import_file(&config, &obj.date, Some(channel.name), &path)?;
+    import_file(&config, &obj.date, Some(channel.name), &path, format)?;
 
     fs::remove_file(path)?;
 
@@ -916,7 +980,8 @@ async fn import_playlist(
 
 /// **Program info**
 ///
-/// Get program infos about given date, or current day
+/// Get program infos about given date, or current day.
+/// Times are in the channel time zone, which is set in the playlist config.
 ///
 /// Examples:
 ///
@@ -943,73 +1008,52 @@ async fn get_program(
     id: web::Path<i32>,
     obj: web::Query<ProgramObj>,
 ) -> Result<impl Responder, ServiceError> {
-    let (config, _) = playout_config(&pool.clone().into_inner(), &id).await?;
-    let start_sec = config.playlist.start_sec.unwrap();
-    let mut days = 0;
-    let mut program = vec![];
-    let after = obj.start_after;
-    let mut before = obj.start_before;
-
-    if after > before {
-        before = chrono::Local
-            .with_ymd_and_hms(after.year(), after.month(), after.day(), 23, 59, 59)
-            .unwrap()
-            .naive_local()
-    }
-
-    if start_sec > time_to_sec(&after.format("%H:%M:%S").to_string()) {
-        days = 1;
-    }
+    let program = read_program(&pool.into_inner(), *id, obj.start_after, obj.start_before).await?;
 
-    let date_range = get_date_range(&vec_strings![
-        (after - Duration::days(days)).format("%Y-%m-%d"),
-        "-",
-        before.format("%Y-%m-%d")
-    ]);
-
-    for date in date_range {
-        let conn = pool.clone().into_inner();
-        let mut naive = NaiveDateTime::parse_from_str(
-            &format!("{date} {}", sec_to_time(start_sec)),
-            "%Y-%m-%d %H:%M:%S%.3f",
-        )
-        .unwrap();
-
-        let playlist = match read_playlist(&conn, *id, date.clone()).await {
-            Ok(p) => p,
-            Err(e) => {
-                error!("Error in Playlist from {date}: {e}");
-                continue;
-            }
-        };
+    Ok(web::Json(program))
+}
 
-        for item in playlist.program {
-            let start: DateTime<Local> = Local.from_local_datetime(&naive).unwrap();
+/// **Program as XMLTV**
+///
+/// Get the program in XMLTV format, for set-top boxes and websites.
+/// Takes the same parameters like the program info.
+///
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/api/program/1/xmltv?start_after=2022-11-13T10:00:00 \
+/// -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[get("/program/{id}/xmltv")]
+#[has_any_role("Role::Admin", "Role::User", type = "Role")]
+async fn get_program_xmltv(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    obj: web::Query<ProgramObj>,
+) -> Result<HttpResponse, ServiceError> {
+    let channel = handles::select_channel(&pool.clone().into_inner(), &id).await?;
+    let program = read_program(&pool.into_inner(), *id, obj.start_after, obj.start_before).await?;
 
-            let source = match Regex::new(&config.text.regex)
-                .ok()
-                .and_then(|r| r.captures(&item.source))
-            {
-                Some(t) => t[1].to_string(),
-                None => item.source,
-            };
-
-            let p_item = ProgramItem {
-                source,
-                start: start.format("%Y-%m-%d %H:%M:%S%.3f%:z").to_string(),
-                r#in: item.seek,
-                out: item.out,
-                duration: item.duration,
-                category: item.category,
-            };
-
-            if naive >= after && naive <= before {
-                program.push(p_item);
-            }
+    Ok(HttpResponse::Ok()
+        .content_type("application/xml; charset=utf-8")
+        .body(xmltv(*id, &channel.name, &program)))
+}
 
-            naive += Duration::milliseconds(((item.out - item.seek) * 1000.0) as i64);
-        }
-    }
+/// **Program as EIT**
+///
+/// Get the program as JSON list of events, in the style of a DVB event information table.
+/// Start times are in UTC.
+///
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/api/program/1/eit?start_after=2022-11-13T10:00:00 \
+/// -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[get("/program/{id}/eit")]
+#[has_any_role("Role::Admin", "Role::User", type = "Role")]
+async fn get_program_eit(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    obj: web::Query<ProgramObj>,
+) -> Result<impl Responder, ServiceError> {
+    let program = read_program(&pool.into_inner(), *id, obj.start_after, obj.start_before).await?;
 
-    Ok(web::Json(program))
+    Ok(web::Json(eit(&program)))
 }
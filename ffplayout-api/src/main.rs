@@ -17,11 +17,11 @@ use api::{
     auth,
     routes::{
         add_channel, add_dir, add_preset, add_user, control_playout, del_playlist, delete_preset,
-        file_browser, gen_playlist, get_all_channels, get_channel, get_log, get_playlist,
-        get_playout_config, get_presets, get_program, get_user, import_playlist, login,
-        media_current, media_last, media_next, move_rename, patch_channel, process_control, remove,
-        remove_channel, save_file, save_playlist, send_text_message, update_playout_config,
-        update_preset, update_user,
+        export_playlist_file, file_browser, gen_playlist, get_all_channels, get_channel, get_log,
+        get_playlist, get_playout_config, get_presets, get_program, get_program_eit,
+        get_program_xmltv, get_user, import_playlist, login, media_current, media_last, media_next,
+        move_rename, patch_channel, process_control, remove, remove_channel, save_file,
+        save_playlist, send_text_message, update_playout_config, update_preset, update_user,
     },
 };
 use db::{db_pool, models::LoginUser};
@@ -123,6 +123,7 @@ async fn main() -> std::io::Result<()> {
                         .service(media_last)
                         .service(process_control)
                         .service(get_playlist)
+                        .service(export_playlist_file)
                         .service(save_playlist)
                         .service(gen_playlist)
                         .service(del_playlist)
@@ -133,7 +134,9 @@ async fn main() -> std::io::Result<()> {
                         .service(remove)
                         .service(save_file)
                         .service(import_playlist)
-                        .service(get_program),
+                        .service(get_program)
+                        .service(get_program_xmltv)
+                        .service(get_program_eit),
                 )
                 .service(Files::new("/", public_path()).index_file("index.html"))
         })
@@ -28,6 +28,14 @@ struct TextParams {
 #[derive(Debug, Deserialize, Serialize, Clone)]
 struct ControlParams {
     control: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -49,6 +57,14 @@ impl<T> RpcObj<T> {
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Process {
     pub command: String,
+    #[serde(default)]
+    pub index: Option<usize>,
+    #[serde(default)]
+    pub time: Option<String>,
+    #[serde(default)]
+    pub position: Option<f64>,
+    #[serde(default)]
+    pub mode: Option<String>,
 }
 
 struct SystemD {
@@ -175,9 +191,16 @@ pub async fn send_message(
 pub async fn control_state(
     conn: &Pool<Sqlite>,
     id: i32,
-    command: String,
+    process: Process,
 ) -> Result<Response, ServiceError> {
-    let json_obj = RpcObj::new(id, "player".into(), ControlParams { control: command });
+    let params = ControlParams {
+        control: process.command,
+        index: process.index,
+        time: process.time,
+        position: process.position,
+        mode: process.mode,
+    };
+    let json_obj = RpcObj::new(id, "player".into(), params);
 
     post_request(conn, id, json_obj).await
 }
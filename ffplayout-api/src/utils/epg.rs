@@ -0,0 +1,64 @@
+use chrono::{Duration, NaiveDateTime};
+use simplelog::*;
+use sqlx::{Pool, Sqlite};
+
+use crate::utils::{errors::ServiceError, playlist::read_playlist, playout_config};
+use ffplayout_lib::{
+    utils::{
+        epg::{program_items, ProgramItem},
+        get_date_range, time_to_sec,
+    },
+    vec_strings,
+};
+
+/// Read program
+///
+/// Collect all clips from the playlists, which starts between `after` and `before`.
+/// The start times come from the same timing like in the playout.
+pub async fn read_program(
+    conn: &Pool<Sqlite>,
+    id: i32,
+    after: NaiveDateTime,
+    mut before: NaiveDateTime,
+) -> Result<Vec<ProgramItem>, ServiceError> {
+    let (config, _) = playout_config(conn, &id).await?;
+    let start_sec = config.playlist.start_sec.unwrap();
+    let mut days = 0;
+    let mut program = vec![];
+
+    if after > before {
+        before = after.date().and_hms_opt(23, 59, 59).unwrap();
+    }
+
+    if start_sec > time_to_sec(&after.format("%H:%M:%S").to_string()) {
+        days = 1;
+    }
+
+    let date_range = get_date_range(&vec_strings![
+        (after - Duration::days(days)).format("%Y-%m-%d"),
+        "-",
+        before.format("%Y-%m-%d")
+    ]);
+
+    for date in date_range {
+        let mut playlist = match read_playlist(conn, id, date.clone()).await {
+            Ok(p) => p,
+            Err(e) => {
+                error!("Error in Playlist from {date}: {e}");
+                continue;
+            }
+        };
+
+        playlist.date = date;
+
+        for item in program_items(&config, playlist) {
+            let naive = item.begin.naive_local();
+
+            if naive >= after && naive <= before {
+                program.push(item);
+            }
+        }
+    }
+
+    Ok(program)
+}
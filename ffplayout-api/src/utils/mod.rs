@@ -16,6 +16,7 @@ use sqlx::{Pool, Sqlite};
 pub mod args_parse;
 pub mod channels;
 pub mod control;
+pub mod epg;
 pub mod errors;
 pub mod files;
 pub mod playlist;
@@ -50,7 +50,9 @@ fn server_monitor(
             warn!("<bright black>[Server]</> {}", line.replace("[error] ", ""));
         }
 
-        if line.contains("Address already in use") {
+        if line.contains("Address already in use")
+            && !proc_ctl.failed(Ingest, "address already in use")
+        {
             proc_ctl.kill_all();
         }
 
@@ -165,6 +167,8 @@ pub fn ingest_server(
         if let Err(e) = error_reader_thread.join() {
             error!("{e:?}");
         };
+
+        proc_control.restart(Ingest);
     }
 
     Ok(())
@@ -1,6 +1,7 @@
 use std::{
+    collections::VecDeque,
     sync::{
-        atomic::{AtomicBool, AtomicUsize},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc, Mutex,
     },
     thread,
@@ -19,6 +20,47 @@ pub use ingest::ingest_server;
 pub use playlist::CurrentProgram;
 
 use ffplayout_lib::utils::folder::FolderSource;
+use playlist::gen_source;
+
+/// Wrap source iterator, so that clips from the RPC insert command are played first,
+/// and clips from the filler folder, which fill a gap, are played one after the other.
+struct QueuedSource {
+    config: PlayoutConfig,
+    playout_stat: PlayoutStatus,
+    source: Box<dyn Iterator<Item = Media>>,
+    fillers: VecDeque<Media>,
+}
+
+impl Iterator for QueuedSource {
+    type Item = Media;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let insert = self.playout_stat.insert.lock().unwrap().take();
+
+        if let Some(node) = insert {
+            info!("Play inserted clip: <b><magenta>{}</></b>", node.source);
+            self.fillers.clear();
+
+            return Some(gen_source(&self.config, node, &self.playout_stat.chain));
+        }
+
+        // playlist gets initialized again, the gap is gone
+        if self.config.processing.mode == Playlist
+            && self.playout_stat.list_init.load(Ordering::SeqCst)
+        {
+            self.fillers.clear();
+        }
+
+        if let Some(filler) = self.fillers.pop_front() {
+            return Some(gen_source(&self.config, filler, &self.playout_stat.chain));
+        }
+
+        let mut node = self.source.next()?;
+        self.fillers.extend(node.filler_queue.drain(..));
+
+        Some(node)
+    }
+}
 
 /// Create a source iterator from playlist, or from folder.
 pub fn source_generator(
@@ -28,7 +70,9 @@ pub fn source_generator(
     playout_stat: PlayoutStatus,
     is_terminated: Arc<AtomicBool>,
 ) -> Box<dyn Iterator<Item = Media>> {
-    match config.processing.mode {
+    let stat = playout_stat.clone();
+
+    let source = match config.processing.mode {
         Folder => {
             info!("Playout in folder mode");
             debug!(
@@ -52,5 +96,12 @@ pub fn source_generator(
 
             Box::new(program) as Box<dyn Iterator<Item = Media>>
         }
-    }
+    };
+
+    Box::new(QueuedSource {
+        config,
+        playout_stat: stat,
+        source,
+        fillers: VecDeque::new(),
+    })
 }
@@ -5,17 +5,83 @@ use std::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc, Mutex,
     },
+    thread,
 };
 
+use chrono::{Duration, NaiveDate};
 use serde_json::json;
 use simplelog::*;
 
 use ffplayout_lib::utils::{
-    check_sync, gen_dummy, get_delta, get_sec, is_close, is_remote, json_serializer::read_json,
-    loop_filler, loop_image, modified_time, seek_and_length, valid_source, Media, MediaProbe,
-    PlayoutConfig, PlayoutStatus, DUMMY_LEN, IMAGE_FORMAT,
+    check_sync, day_offset, fill_gap, gen_dummy, get_delta, get_sec, is_close, is_filler_pool,
+    is_remote, json_serializer::read_json, live_input, loop_filler, loop_image, modified_time,
+    prefetch_remote_playlist, seek_and_length, set_day_times, valid_source, FrameRate, FrameTime,
+    Media, MediaProbe, PlayoutConfig, PlayoutStatus, DUMMY_LEN, IMAGE_FORMAT,
 };
 
+/// Seconds before day change, in which the next remote playlist gets prefetched.
+const PREFETCH_TIME: f64 = 1800.0;
+
+/// Number of clips, which are changed, added or removed on playlist reload.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct ProgramChanges {
+    pub changed: usize,
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// Merge reloaded program into the running list.
+///
+/// All clips before `index`, the played ones and the current one, stay untouched.
+/// The new program continues after the current clip, found by its source and begin,
+/// or by the first clip which starts after the current clip ends.
+pub fn merge_program(
+    current: &[Media],
+    new: Vec<Media>,
+    index: usize,
+) -> (Vec<Media>, ProgramChanges) {
+    let index = index.min(current.len());
+    let mut changes = ProgramChanges::default();
+
+    let start = match index.checked_sub(1).and_then(|i| current.get(i)) {
+        Some(playing) => {
+            let begin = playing.begin.unwrap_or_default();
+            let end = begin + playing.out - playing.seek;
+
+            new.iter()
+                .position(|m| m == playing && is_close(m.begin.unwrap_or_default(), begin, 1.0))
+                .map(|p| p + 1)
+                .or_else(|| {
+                    new.iter()
+                        .position(|m| m.begin.unwrap_or_default() >= end - 1.0)
+                })
+                .unwrap_or(new.len())
+        }
+        None => 0,
+    };
+
+    let new_tail: Vec<Media> = new.into_iter().skip(start).collect();
+    let old_tail = &current[index..];
+
+    for i in 0..old_tail.len().max(new_tail.len()) {
+        match (old_tail.get(i), new_tail.get(i)) {
+            (Some(old), Some(new)) if old != new => changes.changed += 1,
+            (Some(_), None) => changes.removed += 1,
+            (None, Some(_)) => changes.added += 1,
+            _ => (),
+        }
+    }
+
+    let mut program = current[..index].to_vec();
+    program.extend(new_tail);
+
+    for (i, item) in program.iter_mut().enumerate() {
+        item.index = Some(i);
+    }
+
+    (program, changes)
+}
+
 /// Struct for current playlist.
 ///
 /// Here we prepare the init clip and build a iterator where we pull our clips.
@@ -26,6 +92,7 @@ pub struct CurrentProgram {
     json_mod: Option<String>,
     json_path: Option<String>,
     json_date: String,
+    prefetch_date: String,
     pub nodes: Arc<Mutex<Vec<Media>>>,
     current_node: Media,
     index: Arc<AtomicUsize>,
@@ -42,6 +109,8 @@ impl CurrentProgram {
         global_index: Arc<AtomicUsize>,
     ) -> Self {
         let json = read_json(config, None, is_terminated.clone(), true, 0.0);
+        let mut config = config.clone();
+        set_day_times(&mut config, &json.date);
 
         if let Some(file) = &json.current_file {
             info!("Read Playlist: <b><magenta>{}</></b>", file);
@@ -63,11 +132,12 @@ impl CurrentProgram {
         }
 
         Self {
-            config: config.clone(),
+            config,
             start_sec: json.start_sec.unwrap(),
             json_mod: json.modified,
             json_path: json.current_file,
             json_date: json.date,
+            prefetch_date: String::new(),
             nodes: current_list,
             current_node: Media::new(0, "", false),
             index: global_index,
@@ -85,6 +155,8 @@ impl CurrentProgram {
                 info!("Read Playlist: <b><magenta>{}</></b>", file);
             }
 
+            set_day_times(&mut self.config, &json.date);
+            self.start_sec = json.start_sec.unwrap();
             self.json_path = json.current_file;
             self.json_mod = json.modified;
             *self.nodes.lock().unwrap() = json.program;
@@ -93,7 +165,10 @@ impl CurrentProgram {
         {
             let mod_time = modified_time(&self.json_path.clone().unwrap());
 
-            if self.json_mod != mod_time {
+            // when remote server is not reachable, we keep the current playlist
+            let unreachable = mod_time.is_none() && is_remote(&self.json_path.clone().unwrap());
+
+            if self.json_mod != mod_time && !unreachable {
                 // when playlist has changed, reload it
                 info!(
                     "Reload playlist <b><magenta>{}</></b>",
@@ -109,9 +184,21 @@ impl CurrentProgram {
                 );
 
                 self.json_mod = json.modified;
-                *self.nodes.lock().unwrap() = json.program;
 
-                self.playout_stat.list_init.store(true, Ordering::SeqCst);
+                if self.playout_stat.list_init.load(Ordering::SeqCst) {
+                    *self.nodes.lock().unwrap() = json.program;
+                } else {
+                    // keep the running clip, and apply only changes after it
+                    let mut nodes = self.nodes.lock().unwrap();
+                    let (program, changes) =
+                        merge_program(&nodes, json.program, self.index.load(Ordering::SeqCst));
+                    *nodes = program;
+
+                    info!(
+                        "Playlist changes after current clip: <yellow>{}</> changed, <yellow>{}</> added, <yellow>{}</> removed",
+                        changes.changed, changes.added, changes.removed
+                    );
+                }
             }
         } else {
             error!(
@@ -149,6 +236,10 @@ impl CurrentProgram {
             next_start += self.config.general.stop_threshold;
         }
 
+        if is_remote(&self.config.playlist.path) && next_start >= target_length - PREFETCH_TIME {
+            self.prefetch_next_playlist();
+        }
+
         if next_start >= target_length
             || is_close(total_delta, 0.0, 2.0)
             || is_close(total_delta, target_length, 2.0)
@@ -179,6 +270,9 @@ impl CurrentProgram {
                 error!("Unable to write status file: {e}");
             };
 
+            // start and length can differ from day to day, when the DST changes
+            set_day_times(&mut self.config, &json.date);
+            self.start_sec = json.start_sec.unwrap();
             self.json_path = json.current_file.clone();
             self.json_mod = json.modified;
             self.json_date = json.date;
@@ -191,6 +285,20 @@ impl CurrentProgram {
         }
     }
 
+    // Load next remote playlist into cache, so it is available even when the server fails on day change.
+    fn prefetch_next_playlist(&mut self) {
+        if let Ok(date) = NaiveDate::parse_from_str(&self.json_date, "%Y-%m-%d") {
+            let next_date = (date + Duration::days(1)).format("%Y-%m-%d").to_string();
+
+            if next_date != self.prefetch_date {
+                let config = self.config.clone();
+                self.prefetch_date = next_date.clone();
+
+                thread::spawn(move || prefetch_remote_playlist(&config, &next_date));
+            }
+        }
+    }
+
     // Check if last and/or next clip is a advertisement.
     fn last_next_ad(&mut self) {
         let index = self.index.load(Ordering::SeqCst);
@@ -214,23 +322,31 @@ impl CurrentProgram {
         let mut time_sec = get_sec();
 
         if time_sec < self.start_sec {
-            time_sec += self.config.playlist.length_sec.unwrap()
+            time_sec += day_offset(&self.config)
         }
 
         time_sec
     }
 
-    // On init or reload we need to seek for the current clip.
-    fn get_current_clip(&mut self) {
-        let mut time_sec = self.get_current_time();
-        let shift = self.playout_stat.time_shift.lock().unwrap();
+    // Time shift from RPC commands, when it belongs to the current playlist.
+    fn get_time_shift(&self) -> f64 {
+        let shift = *self.playout_stat.time_shift.lock().unwrap();
 
-        if *self.playout_stat.current_date.lock().unwrap()
+        match *self.playout_stat.current_date.lock().unwrap()
             == *self.playout_stat.date.lock().unwrap()
-            && *shift != 0.0
         {
-            info!("Shift playlist start for <yellow>{}</> seconds", *shift);
-            time_sec += *shift;
+            true => shift,
+            false => 0.0,
+        }
+    }
+
+    // On init or reload we need to seek for the current clip.
+    fn get_current_clip(&mut self) {
+        let shift = self.get_time_shift();
+        let time_sec = self.get_current_time() + shift;
+
+        if shift != 0.0 {
+            info!("Shift playlist start for <yellow>{shift}</> seconds");
         }
 
         for (i, item) in self.nodes.lock().unwrap().iter_mut().enumerate() {
@@ -248,13 +364,15 @@ impl CurrentProgram {
         self.get_current_clip();
 
         if !self.playout_stat.list_init.load(Ordering::SeqCst) {
-            let time_sec = self.get_current_time();
+            let time_sec = self.get_current_time() + self.get_time_shift();
             let index = self.index.fetch_add(1, Ordering::SeqCst);
 
             // de-instance node to preserve original values in list
             let mut node_clone = self.nodes.lock().unwrap()[index].clone();
 
             node_clone.seek = time_sec - node_clone.begin.unwrap();
+            // playout starts inside the clip, the transition from previous clip is gone
+            node_clone.overlap = None;
             self.current_node =
                 handle_list_init(&self.config, node_clone, &self.playout_stat.chain);
         }
@@ -301,7 +419,7 @@ impl Iterator for CurrentProgram {
                     }
 
                     if self.config.playlist.start_sec.unwrap() > current_time {
-                        current_time += self.config.playlist.length_sec.unwrap() + 1.0;
+                        current_time += day_offset(&self.config) + 1.0;
                     }
 
                     let mut media = Media::new(0, "", false);
@@ -331,12 +449,11 @@ impl Iterator for CurrentProgram {
                 is_last = true
             }
 
-            self.current_node = timed_source(
-                nodes[index].clone(),
-                &self.config,
-                is_last,
-                &self.playout_stat,
-            );
+            let mut node = nodes[index].clone();
+            // previous clip is probed already, the transition takes its streams
+            node.carry_overlap(&self.current_node);
+
+            self.current_node = timed_source(node, &self.config, is_last, &self.playout_stat);
 
             drop(nodes);
             self.last_next_ad();
@@ -405,7 +522,7 @@ impl Iterator for CurrentProgram {
 /// - check begin and length from clip
 /// - return clip only if we are in 24 hours time range
 fn timed_source(
-    node: Media,
+    mut node: Media,
     config: &PlayoutConfig,
     last: bool,
     playout_stat: &PlayoutStatus,
@@ -430,10 +547,16 @@ fn timed_source(
 
         let sync = check_sync(config, shifted_delta);
 
-        if !sync {
+        if !sync && config.general.stop_on_desync {
             new_node.cmd = None;
 
             return new_node;
+        } else if !sync {
+            if let Some(recovered) =
+                recover_sync(config, &mut node, shifted_delta, &playout_stat.chain)
+            {
+                return recovered;
+            }
         }
     }
 
@@ -453,6 +576,57 @@ fn timed_source(
     new_node
 }
 
+/// Bring the playout back in sync, when clip begin is out of sync for `delta` seconds.
+///
+/// When we are behind, the clip gets cut at the begin, or skipped when it is too short.
+/// When we are ahead, a filler plays before the clip.
+/// A cut clip goes on as normal clip, skipped clip and filler are returned ready to play.
+pub fn recover_sync(
+    config: &PlayoutConfig,
+    node: &mut Media,
+    delta: f64,
+    filter_chain: &Option<Arc<Mutex<Vec<String>>>>,
+) -> Option<Media> {
+    if delta < 0.0 && node.out - node.seek + delta >= 1.0 {
+        error!(
+            "Clip begin out of sync for <yellow>{delta:.3}</> seconds, cut clip at the begin: <b><magenta>{}</></b>",
+            node.source
+        );
+
+        node.seek -= delta;
+        node.overlap = None;
+
+        None
+    } else if delta < 0.0 {
+        error!(
+            "Clip begin out of sync for <yellow>{delta:.3}</> seconds, skip clip: <b><magenta>{}</></b>",
+            node.source
+        );
+
+        let mut skipped = node.clone();
+        skipped.process = Some(false);
+        skipped.cmd = Some(vec![]);
+
+        Some(skipped)
+    } else {
+        error!(
+            "Clip begin out of sync for <yellow>{delta:.3}</> seconds, play filler before: <b><magenta>{}</></b>",
+            node.source
+        );
+
+        let mut filler = Media::new(node.index.unwrap_or_default(), "", false);
+        filler.begin = node.begin.map(|b| b - delta);
+        filler.duration = delta;
+        filler.out = delta;
+
+        let mut filler = gen_source(config, filler, filter_chain);
+        node.overlap = None;
+        filler.filler_queue.push(node.clone());
+
+        Some(filler)
+    }
+}
+
 /// Generate the source CMD, or when clip not exist, get a dummy.
 pub fn gen_source(
     config: &PlayoutConfig,
@@ -461,8 +635,12 @@ pub fn gen_source(
 ) -> Media {
     let duration = node.out - node.seek;
 
-    if valid_source(&node.source) {
+    if node.is_live() && !node.source.is_empty() {
+        node.overlap = None;
+        node.cmd = Some(live_input(&node, config.playlist.live_timeout));
+    } else if valid_source(&node.source) {
         node.add_probe();
+        node.check_overlap();
 
         if node
             .source
@@ -476,7 +654,7 @@ pub fn gen_source(
             node.cmd = Some(seek_and_length(&node));
         }
     } else {
-        let probe = MediaProbe::new(&config.storage.filler_clip);
+        node.overlap = None;
 
         if node.source.is_empty() {
             warn!("Generate filler with <yellow>{duration:.2}</> seconds length!");
@@ -484,7 +662,34 @@ pub fn gen_source(
             error!("Source not found: <b><magenta>{}</></b>", node.source);
         }
 
-        if config
+        // gaps under one second get skipped, so they take no clips from the filler folder
+        let mut fillers = match duration < 1.0 {
+            true => vec![],
+            false => fill_gap(config, duration),
+        };
+        let filler_clip = &config.storage.filler_clip;
+        let probe = (!is_filler_pool(config)).then(|| MediaProbe::new(filler_clip));
+
+        if !fillers.is_empty() {
+            // first clip from filler folder plays now, the others get queued after it
+            let first = fillers.remove(0);
+            let mut begin = node.begin.map(|b| b + first.out);
+
+            node.source = first.source;
+            node.duration = first.duration;
+            node.seek = 0.0;
+            node.out = first.out;
+            node.probe = first.probe;
+            node.cmd = Some(seek_and_length(&node));
+
+            for mut filler in fillers {
+                filler.index = node.index;
+                filler.begin = begin;
+                begin = begin.map(|b| b + filler.out);
+
+                node.filler_queue.push(filler);
+            }
+        } else if config
             .storage
             .filler_clip
             .rsplit_once('.')
@@ -494,10 +699,10 @@ pub fn gen_source(
         {
             node.source = config.storage.filler_clip.clone();
             node.cmd = Some(loop_image(&node));
-            node.probe = Some(probe);
+            node.probe = probe;
         } else if let Some(length) = probe
             .clone()
-            .format
+            .and_then(|p| p.format)
             .and_then(|f| f.duration)
             .and_then(|d| d.parse::<f64>().ok())
         {
@@ -506,7 +711,7 @@ pub fn gen_source(
             node.duration = length;
             node.out = duration;
             node.cmd = Some(loop_filler(&node));
-            node.probe = Some(probe);
+            node.probe = probe;
         } else {
             // create colored placeholder.
             let (source, cmd) = gen_dummy(config, duration);
@@ -538,13 +743,14 @@ fn handle_list_init(
 ) -> Media {
     debug!("Playlist init");
     let (_, total_delta) = get_delta(config, &node.begin.unwrap());
-    let mut out = node.out;
+    let rate = FrameRate::from_fps(config.processing.fps);
+    let total = FrameTime::from_sec(total_delta, rate);
 
-    if node.out - node.seek > total_delta {
-        out = total_delta + node.seek;
+    if node.frame_length(rate) > total {
+        // cut on frame boundary
+        node.out = (FrameTime::from_sec(node.seek, rate) + total).sec();
     }
 
-    node.out = out;
     gen_source(config, node, filter_chain)
 }
 
@@ -576,7 +782,9 @@ fn handle_list_end(
 
     if node.duration > total_delta && total_delta > 1.0 && node.duration - node.seek >= total_delta
     {
-        node.out = out;
+        // cut on frame boundary
+        let rate = FrameRate::from_fps(config.processing.fps);
+        node.out = FrameTime::from_sec(out, rate).sec().min(node.duration);
     } else {
         warn!("Playlist is not long enough: <yellow>{total_delta:.2}</> seconds needed");
     }
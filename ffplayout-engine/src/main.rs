@@ -19,8 +19,9 @@ use ffplayout::{
 };
 
 use ffplayout_lib::utils::{
-    generate_playlist, import::import_file, init_logging, send_mail, validate_ffmpeg,
-    OutputMode::*, PlayerControl, PlayoutStatus, ProcessControl,
+    export::export_file, generate_playlist, import::import_file, init_logging, send_mail,
+    set_clock, set_time_zone, validate_ffmpeg, Clock, ClockMode, OutputMode::*, PlayerControl,
+    PlayoutStatus, ProcessControl,
 };
 
 #[cfg(debug_assertions)]
@@ -106,6 +107,25 @@ fn main() {
         exit(1);
     };
 
+    if let Err(e) = set_time_zone(&config.playlist.timezone) {
+        error!("{e}");
+        exit(1);
+    };
+
+    match Clock::from_config(&config) {
+        Ok(clock) => set_clock(clock),
+        Err(e) => {
+            error!("{e}");
+            exit(1);
+        }
+    }
+
+    if config.general.clock == ClockMode::Accelerated && config.out.mode != Null {
+        warn!("Accelerated clock runs in sync only with <yellow>null</> output!");
+    }
+
+    proc_control.supervise(config.general.max_failures, config.general.restart_delay);
+
     let config_clone = config.clone();
 
     if ![2, 4, 6, 8].contains(&config.processing.audio_channels) {
@@ -131,8 +151,33 @@ fn main() {
             exit(1);
         }
 
-        // convert text/m3u file to playlist
-        match import_file(&config, &args.date.unwrap(), None, &path) {
+        // convert text/m3u/csv/xspf/edl file to playlist
+        match import_file(
+            &config,
+            &args.date.unwrap(),
+            None,
+            &path,
+            args.import_format,
+        ) {
+            Ok(m) => {
+                info!("{m}");
+                exit(0);
+            }
+            Err(e) => {
+                error!("{e}");
+                exit(1);
+            }
+        }
+    }
+
+    if let Some(path) = args.export {
+        if args.date.is_none() {
+            error!("Export needs date parameter!");
+            exit(1);
+        }
+
+        // write playlist to m3u/csv/edl file
+        match export_file(&config, &args.date.unwrap(), &path, args.export_format) {
             Ok(m) => {
                 info!("{m}");
                 exit(0);
@@ -0,0 +1,303 @@
+/*
+Ad break signaling
+
+Consecutive clips with category 'advertisement' build one ad break.
+When the first ad clip starts, a cue out is signaled with the planned length from the whole break,
+and when the first clip after the break starts, a cue in.
+
+In HLS mode the cues are written as tags to a copy from the playlist, with '_cue' in its name
+(stream.m3u8 -> stream_cue.m3u8), which is the playlist for the clients. ffmpeg rewrites
+its own playlist on every new segment, so the copy gets updated after each write.
+In the other modes SCTE-35 splice inserts are send as MPEG-TS packets to an UDP address.
+*/
+
+use std::{
+    fs,
+    net::UdpSocket,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, sleep},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, SecondsFormat};
+use simplelog::*;
+
+use ffplayout_lib::utils::{
+    sec_to_time, time_now, Media, OutputMode::*, PlayerControl, PlayoutConfig, SpliceInsert,
+};
+
+/// Keep only the latest cues, older ones are out of the playlist window anyway.
+const MAX_HLS_CUES: usize = 64;
+
+/// Segment in the playlist, where the cue tags belong to.
+#[derive(Debug, Clone, PartialEq)]
+enum CueAnchor {
+    /// Playlist had no segment yet, when the cue was added.
+    Start,
+    After(String),
+    Before(String),
+}
+
+#[derive(Debug, Clone)]
+struct HlsCue {
+    anchor: CueAnchor,
+    tags: Vec<String>,
+}
+
+#[derive(Debug)]
+struct AdBreakState {
+    event_id: u32,
+    start: DateTime<Local>,
+}
+
+pub struct AdBreak {
+    enable: bool,
+    event_id: u32,
+    current: Option<AdBreakState>,
+    udp: Option<(UdpSocket, String)>,
+    pid: u16,
+    continuity_counter: u8,
+    hls_playlist: Option<PathBuf>,
+    hls_cues: Arc<Mutex<Vec<HlsCue>>>,
+}
+
+impl AdBreak {
+    pub fn new(config: &PlayoutConfig, is_terminated: Arc<AtomicBool>) -> Self {
+        let mut ad_break = Self {
+            enable: config.out.cue_markers,
+            event_id: time_now().timestamp() as u32,
+            current: None,
+            udp: None,
+            pid: config.out.scte35_pid,
+            continuity_counter: 0,
+            hls_playlist: None,
+            hls_cues: Arc::new(Mutex::new(vec![])),
+        };
+
+        if !ad_break.enable {
+            return ad_break;
+        }
+
+        if config.out.mode == HLS {
+            ad_break.hls_playlist = config
+                .out
+                .output_cmd
+                .as_ref()
+                .and_then(|cmd| cmd.iter().rev().find(|a| a.ends_with(".m3u8")))
+                .map(PathBuf::from);
+
+            match ad_break.hls_playlist.clone() {
+                Some(playlist) => {
+                    let cues = ad_break.hls_cues.clone();
+
+                    thread::spawn(move || hls_tagger(&playlist, cues, is_terminated));
+                }
+                None => warn!("No HLS playlist found in output, cue markers are disabled"),
+            }
+        } else if !config.out.scte35_address.is_empty() {
+            match UdpSocket::bind("0.0.0.0:0") {
+                Ok(socket) => ad_break.udp = Some((socket, config.out.scte35_address.clone())),
+                Err(e) => error!("Can't open socket for SCTE-35 cues: {e}"),
+            }
+        }
+
+        ad_break
+    }
+
+    /// Signal cue out or cue in, when the clip begins or ends an ad break.
+    pub fn clip_start(&mut self, node: &Media, play_control: &PlayerControl) {
+        if !self.enable {
+            return;
+        }
+
+        let is_ad = node.category == "advertisement";
+
+        if is_ad && self.current.is_none() {
+            let duration = break_length(node, play_control);
+            self.event_id = self.event_id.wrapping_add(1);
+
+            info!(
+                "Ad break <yellow>{}</> begins, planned length: <yellow>{}</>",
+                self.event_id,
+                sec_to_time(duration)
+            );
+
+            let state = AdBreakState {
+                event_id: self.event_id,
+                start: time_now(),
+            };
+
+            let splice = SpliceInsert::cue_out(state.event_id, duration);
+            self.send_scte35(&splice);
+            self.add_hls_cue(vec![
+                format!(
+                    "#EXT-X-DATERANGE:ID=\"{}\",START-DATE=\"{}\",PLANNED-DURATION={duration:.3},SCTE35-OUT={}",
+                    state.event_id,
+                    state.start.to_rfc3339_opts(SecondsFormat::Millis, false),
+                    splice.hex()
+                ),
+                format!("#EXT-X-CUE-OUT:DURATION={duration:.3}"),
+            ]);
+
+            self.current = Some(state);
+        } else if !is_ad {
+            if let Some(state) = self.current.take() {
+                let end = time_now();
+                let splice = SpliceInsert::cue_in(state.event_id);
+
+                info!(
+                    "Ad break <yellow>{}</> ends, was on air for <yellow>{}</>",
+                    state.event_id,
+                    sec_to_time((end - state.start).num_milliseconds() as f64 / 1000.0)
+                );
+
+                self.send_scte35(&splice);
+                self.add_hls_cue(vec![
+                    format!(
+                        "#EXT-X-DATERANGE:ID=\"{}\",START-DATE=\"{}\",END-DATE=\"{}\",SCTE35-IN={}",
+                        state.event_id,
+                        state.start.to_rfc3339_opts(SecondsFormat::Millis, false),
+                        end.to_rfc3339_opts(SecondsFormat::Millis, false),
+                        splice.hex()
+                    ),
+                    "#EXT-X-CUE-IN".to_string(),
+                ]);
+            }
+        }
+    }
+
+    fn send_scte35(&mut self, splice: &SpliceInsert) {
+        if let Some((socket, address)) = &self.udp {
+            let packet = splice.ts_packet(self.pid, self.continuity_counter);
+            self.continuity_counter = (self.continuity_counter + 1) & 0x0F;
+
+            if let Err(e) = socket.send_to(&packet, address) {
+                error!("Send SCTE-35 cue to <b><magenta>{address}</></b> failed: {e}");
+            }
+        }
+    }
+
+    /// Cues in HLS belong before the first segment from the new clip,
+    /// so we remember the last segment, which exists now.
+    fn add_hls_cue(&self, tags: Vec<String>) {
+        if let Some(playlist) = &self.hls_playlist {
+            let anchor = fs::read_to_string(playlist)
+                .ok()
+                .and_then(|p| p.lines().rev().find(|l| is_segment(l)).map(str::to_string))
+                .map_or(CueAnchor::Start, CueAnchor::After);
+            let mut cues = self.hls_cues.lock().unwrap();
+
+            cues.push(HlsCue { anchor, tags });
+
+            if cues.len() > MAX_HLS_CUES {
+                cues.remove(0);
+            }
+        }
+    }
+}
+
+/// Length from the ad break, which starts with this clip.
+fn break_length(node: &Media, play_control: &PlayerControl) -> f64 {
+    let mut length = node.out - node.seek;
+
+    if let Some(index) = node.index {
+        length += play_control
+            .current_list
+            .lock()
+            .unwrap()
+            .iter()
+            .skip(index + 1)
+            .take_while(|n| n.category == "advertisement")
+            .map(|n| n.out - n.seek)
+            .sum::<f64>();
+    }
+
+    length
+}
+
+fn is_segment(line: &str) -> bool {
+    !line.trim().is_empty() && !line.starts_with('#')
+}
+
+/// Insert cue tags to playlist content.
+fn insert_cues(content: &str, cues: &mut Vec<HlsCue>) -> String {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let mut inserts = vec![];
+
+    // cues from the begin belong to the first segment, when there is one
+    if let Some(first) = lines.iter().find(|l| is_segment(l)) {
+        for cue in cues.iter_mut().filter(|c| c.anchor == CueAnchor::Start) {
+            cue.anchor = CueAnchor::Before(first.clone());
+        }
+    }
+
+    // segments from cues, which are not in playlist anymore, are deleted from ffmpeg
+    cues.retain(|cue| match &cue.anchor {
+        CueAnchor::After(segment) | CueAnchor::Before(segment) => {
+            lines.iter().any(|l| l == segment)
+        }
+        CueAnchor::Start => true,
+    });
+
+    for cue in cues.iter() {
+        let position = match &cue.anchor {
+            CueAnchor::After(segment) => lines.iter().position(|l| l == segment).map(|p| p + 1),
+            CueAnchor::Before(segment) => lines
+                .iter()
+                .position(|l| l == segment)
+                .and_then(|p| lines[..p].iter().rposition(|l| l.starts_with("#EXTINF"))),
+            CueAnchor::Start => None,
+        }
+        .unwrap_or(lines.len());
+
+        inserts.push((position, cue.tags.clone()));
+    }
+
+    // insert from the end, so positions stay valid
+    inserts.sort_by_key(|(position, _)| *position);
+
+    for (position, tags) in inserts.into_iter().rev() {
+        lines.splice(position..position, tags);
+    }
+
+    lines.join("\n") + "\n"
+}
+
+/// Path from the tagged playlist, beside the one from ffmpeg.
+fn cue_playlist(playlist: &Path) -> PathBuf {
+    let stem = playlist
+        .file_stem()
+        .map_or("stream".into(), |s| s.to_string_lossy());
+
+    playlist.with_file_name(format!("{stem}_cue.m3u8"))
+}
+
+/// Keep the tagged copy from the HLS playlist up to date.
+///
+/// The playlist from ffmpeg is only read, so there is no race with its own writes.
+fn hls_tagger(playlist: &Path, cues: Arc<Mutex<Vec<HlsCue>>>, is_terminated: Arc<AtomicBool>) {
+    let cue_file = cue_playlist(playlist);
+    let tmp_file = cue_file.with_extension("m3u8.tmp");
+    let mut last_tagged = String::new();
+
+    info!("Write HLS playlist with cue tags to: <b><magenta>{cue_file:?}</></b>");
+
+    while !is_terminated.load(Ordering::SeqCst) {
+        if let Ok(content) = fs::read_to_string(playlist) {
+            let tagged = insert_cues(&content, &mut cues.lock().unwrap());
+
+            if tagged != last_tagged {
+                match fs::write(&tmp_file, &tagged).and_then(|_| fs::rename(&tmp_file, &cue_file)) {
+                    Ok(_) => last_tagged = tagged,
+                    Err(e) => error!("Write cue tags to playlist failed: {e}"),
+                }
+            }
+        }
+
+        sleep(Duration::from_millis(500));
+    }
+}
@@ -19,20 +19,22 @@ out:
 
 use std::{
     io::{BufRead, BufReader, Error},
-    process::{exit, Command, Stdio},
+    process::{exit, ChildStdout, Command, Stdio},
     sync::atomic::Ordering,
     thread::{self, sleep},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use simplelog::*;
 
+use super::live::{is_killed, LiveEvent};
+use super::{cue::AdBreak, slate_node};
 use crate::input::{ingest::log_line, source_generator};
 use crate::utils::prepare_output_cmd;
 use ffplayout_lib::{
     utils::{
-        controller::ProcessUnit::*, sec_to_time, stderr_reader, test_tcp_port, Media,
-        PlayerControl, PlayoutConfig, PlayoutStatus, ProcessControl,
+        clock, controller::ProcessUnit::*, sec_to_time, stderr_reader, test_tcp_port, Media,
+        PlayerControl, PlayoutConfig, PlayoutStatus, ProcessControl, ProcessMode::*,
     },
     vec_strings,
 };
@@ -98,6 +100,12 @@ fn ingest_to_hls_server(
                 };
             }
 
+            if line.contains("Address already in use")
+                && !proc_control.failed(Ingest, "address already in use")
+            {
+                proc_control.kill_all();
+            }
+
             if !is_running {
                 proc_control.server_is_running.store(true, Ordering::SeqCst);
                 playlist_init.store(true, Ordering::SeqCst);
@@ -128,6 +136,8 @@ fn ingest_to_hls_server(
         if proc_control.is_terminated.load(Ordering::SeqCst) {
             break;
         }
+
+        proc_control.restart(Ingest);
     }
 
     Ok(())
@@ -136,6 +146,9 @@ fn ingest_to_hls_server(
 /// HLS Writer
 ///
 /// Write with single ffmpeg instance directly to a HLS playlist.
+///
+/// Live items are written from [LiveEvent], here the stream counts as on air
+/// from the first frame, which the writer has encoded.
 pub fn write_hls(
     config: &PlayoutConfig,
     play_control: PlayerControl,
@@ -145,7 +158,10 @@ pub fn write_hls(
     let config_clone = config.clone();
     let ff_log_format = format!("level+{}", config.logging.ffmpeg_level.to_lowercase());
     let play_stat = playout_stat.clone();
+    let playlist_init = playout_stat.list_init.clone();
     let proc_control_c = proc_control.clone();
+    let mut ad_break = AdBreak::new(config, proc_control.is_terminated.clone());
+    let chain = playout_stat.chain.clone();
 
     let get_source = source_generator(
         config.clone(),
@@ -161,57 +177,53 @@ pub fn write_hls(
     }
 
     for node in get_source {
-        *play_control.current_media.lock().unwrap() = Some(node.clone());
-
-        let mut cmd = match node.cmd {
-            Some(cmd) => cmd,
-            None => break,
-        };
+        if node.cmd.is_none() {
+            break;
+        }
 
         if !node.process.unwrap() {
             continue;
         }
 
-        info!(
-            "Play for <yellow>{}</>: <b><magenta>{}</></b>",
-            sec_to_time(node.out - node.seek),
-            node.source
-        );
+        let mut live_event = None;
+        let mut next = Some(node);
 
-        let mut enc_prefix = vec_strings!["-hide_banner", "-nostats", "-v", &ff_log_format];
-        enc_prefix.append(&mut cmd);
-        let enc_cmd = prepare_output_cmd(config, enc_prefix, &node.filter);
+        while let Some(node) = next.take() {
+            *play_control.current_media.lock().unwrap() = Some(node.clone());
 
-        debug!(
-            "HLS writer CMD: <bright-blue>\"ffmpeg {}\"</>",
-            enc_cmd.join(" ")
-        );
+            if node.is_live() && live_event.is_none() {
+                live_event = Some(LiveEvent::new(config, &node, &chain));
+            }
 
-        let mut enc_proc = match Command::new("ffmpeg")
-            .args(enc_cmd)
-            .stderr(Stdio::piped())
-            .spawn()
-        {
-            Err(e) => {
-                error!("couldn't spawn encoder process: {e}");
-                panic!("couldn't spawn encoder process: {e}")
+            ad_break.clip_start(&node, &play_control);
+
+            let on_air_since = write_node(config, &node, &ff_log_format, &proc_control);
+
+            // failed encoder, play on at the current position after the restart delay
+            if proc_control.restart(Encoder) && config.processing.mode == Playlist {
+                playlist_init.store(true, Ordering::SeqCst);
             }
-            Ok(proc) => proc,
-        };
 
-        let enc_err = BufReader::new(enc_proc.stderr.take().unwrap());
-        *proc_control.encoder_term.lock().unwrap() = Some(enc_proc);
+            if let Some(event) = live_event.as_mut() {
+                let on_air =
+                    on_air_since.map_or(0.0, |t| t.elapsed().as_secs_f64() * clock().speed());
+                let stopped = is_killed(&proc_control.encoder_term)
+                    || proc_control.is_terminated.load(Ordering::SeqCst);
 
-        if let Err(e) = stderr_reader(enc_err, Encoder, proc_control.clone()) {
-            error!("{e:?}")
-        };
+                next = event.next_node(&node, on_air, stopped);
+            }
 
-        if let Err(e) = proc_control.wait(Encoder) {
-            error!("{e}");
-        }
+            while proc_control.server_is_running.load(Ordering::SeqCst) {
+                sleep(Duration::from_secs(1));
+            }
 
-        while proc_control.server_is_running.load(Ordering::SeqCst) {
-            sleep(Duration::from_secs(1));
+            // on hold, write slate until release
+            while play_control.hold.lock().unwrap().is_some() {
+                let slate = slate_node(config, &chain);
+                *play_control.current_media.lock().unwrap() = Some(slate.clone());
+
+                write_node(config, &slate, &ff_log_format, &proc_control);
+            }
         }
     }
 
@@ -219,3 +231,84 @@ pub fn write_hls(
 
     proc_control.kill_all();
 }
+
+/// Run the HLS writer for one clip, until it is done.
+///
+/// For live streams the time is returned, from which the first frame was encoded.
+fn write_node(
+    config: &PlayoutConfig,
+    node: &Media,
+    ff_log_format: &str,
+    proc_control: &ProcessControl,
+) -> Option<Instant> {
+    let mut cmd = node.cmd.clone().unwrap_or_default();
+
+    info!(
+        "Play for <yellow>{}</>: <b><magenta>{}</></b>",
+        sec_to_time(node.out - node.seek),
+        node.source
+    );
+
+    let is_live = node.is_live();
+    let mut enc_prefix = vec_strings!["-hide_banner", "-nostats", "-v", ff_log_format];
+
+    if is_live {
+        // progress shows, when frames from the stream arrive
+        enc_prefix.append(&mut vec_strings!["-progress", "pipe:1"]);
+    }
+
+    enc_prefix.append(&mut cmd);
+    let enc_cmd = prepare_output_cmd(config, enc_prefix, &node.filter);
+
+    debug!(
+        "HLS writer CMD: <bright-blue>\"ffmpeg {}\"</>",
+        enc_cmd.join(" ")
+    );
+
+    let mut enc_proc = match Command::new("ffmpeg")
+        .args(enc_cmd)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Err(e) => {
+            error!("couldn't spawn encoder process: {e}");
+            panic!("couldn't spawn encoder process: {e}")
+        }
+        Ok(proc) => proc,
+    };
+
+    let enc_out = BufReader::new(enc_proc.stdout.take().unwrap());
+    let enc_err = BufReader::new(enc_proc.stderr.take().unwrap());
+    *proc_control.encoder_term.lock().unwrap() = Some(enc_proc);
+
+    let progress_thread = thread::spawn(move || first_frame(enc_out));
+
+    if let Err(e) = stderr_reader(enc_err, Encoder, is_live, proc_control.clone()) {
+        error!("{e:?}")
+    };
+
+    if let Err(e) = proc_control.wait(Encoder) {
+        error!("{e}");
+    }
+
+    progress_thread.join().ok().flatten()
+}
+
+/// Read ffmpeg progress, until the end, and get the time from the first encoded frame.
+fn first_frame(progress: BufReader<ChildStdout>) -> Option<Instant> {
+    let mut since = None;
+
+    for line in progress.lines().map_while(Result::ok) {
+        if since.is_none()
+            && line
+                .strip_prefix("frame=")
+                .and_then(|f| f.trim().parse::<u64>().ok())
+                .map_or(false, |f| f > 0)
+        {
+            since = Some(Instant::now());
+        }
+    }
+
+    since
+}
@@ -0,0 +1,137 @@
+/*
+Scheduled live events
+
+A playlist item with "type": "live" plays a stream URL for its length.
+When the stream not connects in time, or drops, the remaining time of the event
+is filled with filler, and after 'live_retry' seconds the stream gets connected again.
+The time, where the stream was really on air, gets logged when the event ends.
+*/
+
+use std::{
+    process::Child,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use simplelog::*;
+
+use crate::input::playlist::gen_source;
+use ffplayout_lib::utils::{clock, sec_to_time, Media, PlayoutConfig};
+
+/// Stream must be on air at least this long, to reconnect without filler in between.
+const MIN_ON_AIR: f64 = 1.0;
+
+pub struct LiveEvent {
+    config: PlayoutConfig,
+    chain: Option<Arc<Mutex<Vec<String>>>>,
+    source: Media,
+    end: Instant,
+    on_air: f64,
+    connects: usize,
+}
+
+impl LiveEvent {
+    /// Live event begins now, with the length from the playlist item.
+    pub fn new(
+        config: &PlayoutConfig,
+        node: &Media,
+        chain: &Option<Arc<Mutex<Vec<String>>>>,
+    ) -> Self {
+        let length = node.out - node.seek;
+
+        info!(
+            "Live event begins, planned length <yellow>{}</>: <b><magenta>{}</></b>",
+            sec_to_time(length),
+            node.source
+        );
+
+        Self {
+            config: config.clone(),
+            chain: chain.clone(),
+            source: node.clone(),
+            end: Instant::now() + Duration::from_secs_f64(length.max(0.0) / clock().speed()),
+            on_air: 0.0,
+            connects: 1,
+        }
+    }
+
+    /// Remaining playout time from the event, in seconds.
+    pub fn remaining(&self) -> f64 {
+        self.end
+            .saturating_duration_since(Instant::now())
+            .as_secs_f64()
+            * clock().speed()
+    }
+
+    /// Clip from the event has ended, get the next one for the remaining time.
+    ///
+    /// `on_air` is the time, in which the stream has send data. When the clip was stopped
+    /// from outside, or the time is over, the event ends and None is returned.
+    pub fn next_node(&mut self, last: &Media, on_air: f64, stopped: bool) -> Option<Media> {
+        let remaining = self.remaining();
+
+        if last.is_live() {
+            self.on_air += on_air;
+        }
+
+        if stopped || remaining < 1.0 {
+            self.finish();
+
+            return None;
+        }
+
+        let mut node = self.source.clone();
+
+        if last.is_live() && on_air >= MIN_ON_AIR {
+            warn!(
+                "Live stream dropped, reconnect: <b><magenta>{}</></b>",
+                node.source
+            );
+        } else if last.is_live() {
+            let retry = self.config.playlist.live_retry.max(1.0).min(remaining);
+
+            warn!(
+                "Live stream not available, play filler for <yellow>{retry:.2}</> seconds: <b><magenta>{}</></b>",
+                node.source
+            );
+
+            node.source = String::new();
+            node.item_type = String::new();
+            node.duration = retry;
+            node.out = retry;
+        }
+
+        if node.is_live() {
+            node.duration = remaining;
+            node.out = remaining;
+            self.connects += 1;
+        }
+
+        node.seek = 0.0;
+        node.cmd = None;
+        node.filter = None;
+        node.probe = None;
+
+        Some(gen_source(&self.config, node, &self.chain))
+    }
+
+    fn finish(&self) {
+        let planned = self.source.out - self.source.seek;
+
+        info!(
+            "Live event ends, was on air for <yellow>{}</> from <yellow>{}</>, connection attempts: <yellow>{}</>",
+            sec_to_time(self.on_air.min(planned)),
+            sec_to_time(planned),
+            self.connects
+        );
+    }
+}
+
+/// Check if process was stopped with a signal, like it happens on skipping or switching to ingest.
+pub fn is_killed(proc: &Mutex<Option<Child>>) -> bool {
+    proc.lock()
+        .unwrap()
+        .as_mut()
+        .and_then(|p| p.try_wait().ok().flatten())
+        .map_or(false, |status| status.code().is_none())
+}
@@ -1,27 +1,323 @@
 use std::{
-    io::{prelude::*, BufReader, BufWriter, Read},
-    process::{Command, Stdio},
-    sync::atomic::Ordering,
-    thread::{self, sleep},
-    time::Duration,
+    io::{prelude::*, BufReader, BufWriter, Error, Read},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, sleep, JoinHandle},
+    time::{Duration, Instant},
 };
 
-use crossbeam_channel::bounded;
+use crossbeam_channel::{bounded, Receiver};
 use simplelog::*;
 
+mod cue;
 mod desktop;
 mod hls;
+mod live;
 mod null;
 mod stream;
 
 pub use hls::write_hls;
 
 use crate::input::{ingest_server, source_generator};
+use cue::AdBreak;
 use ffplayout_lib::utils::{
-    sec_to_time, stderr_reader, OutputMode::*, PlayerControl, PlayoutConfig, PlayoutStatus,
-    ProcessControl, ProcessUnit::*,
+    clock, gen_dummy, loop_filler, loop_image, sec_to_time, stderr_reader, with_look_ahead, Media,
+    OutputMode::*, PlayerControl, PlayoutConfig, PlayoutStatus, ProcessControl, ProcessMode::*,
+    ProcessUnit::*, DUMMY_LEN, IMAGE_FORMAT,
 };
 use ffplayout_lib::vec_strings;
+use live::{is_killed, LiveEvent};
+
+/// Decoder instance, which output is read in a separate thread.
+///
+/// The output is buffered in a bounded channel, so the decoder from the next clip
+/// can start before the current clip ends, without blocking the encoder.
+struct DecoderUnit {
+    node: Media,
+    index: usize,
+    list_source: Option<String>,
+    proc: Option<Child>,
+    receiver: Receiver<(usize, [u8; 65088])>,
+    reader_thread: JoinHandle<Result<(), Error>>,
+    error_thread: JoinHandle<Result<(), Error>>,
+}
+
+impl DecoderUnit {
+    fn new(
+        config: &PlayoutConfig,
+        node: Media,
+        index: usize,
+        ff_log_format: &str,
+        proc_control: &ProcessControl,
+    ) -> Self {
+        let mut dec_cmd = vec_strings!["-hide_banner", "-nostats", "-v", ff_log_format];
+        dec_cmd.append(&mut node.cmd.clone().unwrap_or_default());
+
+        if let Some(mut filter) = node.filter.clone() {
+            dec_cmd.append(&mut filter.cmd());
+            dec_cmd.append(&mut filter.map());
+        }
+
+        if let Some(mut cmd) = config.processing.cmd.clone() {
+            dec_cmd.append(&mut cmd);
+        }
+
+        debug!(
+            "Decoder CMD: <bright-blue>\"ffmpeg {}\"</>",
+            dec_cmd.join(" ")
+        );
+
+        // create ffmpeg decoder instance, for reading the input files
+        let mut dec_proc = match Command::new("ffmpeg")
+            .args(dec_cmd)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+        {
+            Ok(proc) => proc,
+            Err(e) => {
+                error!("couldn't spawn decoder process: {}", e);
+                panic!("couldn't spawn decoder process: {}", e)
+            }
+        };
+
+        let mut dec_reader = BufReader::new(dec_proc.stdout.take().unwrap());
+        let dec_err = BufReader::new(dec_proc.stderr.take().unwrap());
+        let dec_p_ctl = proc_control.clone();
+        let (sender, receiver) = bounded(96);
+
+        let reader_thread = thread::spawn(move || {
+            let mut buffer = [0; 65088];
+
+            loop {
+                let dec_bytes_len = dec_reader.read(&mut buffer[..])?;
+
+                if dec_bytes_len == 0 || sender.send((dec_bytes_len, buffer)).is_err() {
+                    return Ok(());
+                }
+            }
+        });
+
+        let is_live = node.is_live();
+        let error_thread =
+            thread::spawn(move || stderr_reader(dec_err, Decoder, is_live, dec_p_ctl));
+
+        Self {
+            node,
+            index,
+            list_source: None,
+            proc: Some(dec_proc),
+            receiver,
+            reader_thread,
+            error_thread,
+        }
+    }
+
+    fn is_valid(&self, play_control: &PlayerControl, playlist_init: &AtomicBool) -> bool {
+        pre_roll_valid(play_control, playlist_init, self.index, &self.list_source)
+    }
+
+    /// Wait until decoder is finished, returns false on reading errors.
+    fn finish(self) -> bool {
+        let Self {
+            receiver,
+            reader_thread,
+            error_thread,
+            ..
+        } = self;
+        let mut success = true;
+
+        drop(receiver);
+
+        match reader_thread.join() {
+            Ok(Err(e)) => {
+                error!("Reading error from decoder: {e:?}");
+                success = false;
+            }
+            Err(e) => error!("{e:?}"),
+            _ => (),
+        }
+
+        if let Err(e) = error_thread.join() {
+            error!("{e:?}");
+        };
+
+        success
+    }
+
+    /// Stop pre-rolled decoder, which is not needed anymore.
+    fn discard(mut self) {
+        debug!(
+            "Discard pre-rolled clip: <b><magenta>{}</></b>",
+            self.node.source
+        );
+
+        if let Some(mut proc) = self.proc.take() {
+            if let Err(e) = proc.kill() {
+                error!("Decoder {e:?}")
+            };
+
+            if let Err(e) = proc.wait() {
+                error!("Decoder {e:?}")
+            };
+        }
+
+        self.finish();
+    }
+}
+
+/// Slate for hold mode, it plays in chunks until the hold is released.
+///
+/// When no slate is set, or it is not usable, a dummy clip is used.
+pub fn slate_node(config: &PlayoutConfig, chain: &Option<Arc<Mutex<Vec<String>>>>) -> Media {
+    let slate = &config.storage.slate_clip;
+    let mut node = Media::new(0, slate, !slate.is_empty());
+    node.out = DUMMY_LEN;
+
+    if Path::new(slate).is_file()
+        && slate
+            .rsplit_once('.')
+            .map(|(_, e)| e.to_lowercase())
+            .filter(|c| IMAGE_FORMAT.contains(&c.as_str()))
+            .is_some()
+    {
+        node.cmd = Some(loop_image(&node));
+    } else if node.duration > 0.0 {
+        node.cmd = Some(loop_filler(&node));
+    } else {
+        let (source, cmd) = gen_dummy(config, DUMMY_LEN);
+        node.source = source;
+        node.cmd = Some(cmd);
+    }
+
+    node.duration = DUMMY_LEN;
+    node.add_filter(config, chain);
+
+    node
+}
+
+/// Start encoder, its messages get logged in a separate thread.
+fn start_encoder(
+    config: &PlayoutConfig,
+    ff_log_format: &str,
+    proc_control: &ProcessControl,
+) -> (BufWriter<ChildStdin>, JoinHandle<Result<(), Error>>) {
+    let mut enc_proc = match config.out.mode {
+        Desktop => desktop::output(config, ff_log_format),
+        Null => null::output(config, ff_log_format),
+        Stream => stream::output(config, ff_log_format),
+        _ => panic!("Output mode doesn't exists!"),
+    };
+
+    let enc_writer = BufWriter::new(enc_proc.stdin.take().unwrap());
+    let enc_err = BufReader::new(enc_proc.stderr.take().unwrap());
+
+    *proc_control.encoder_term.lock().unwrap() = Some(enc_proc);
+    let enc_p_ctl = proc_control.clone();
+
+    // spawn a thread to log ffmpeg output error messages
+    let error_encoder_thread =
+        thread::spawn(move || stderr_reader(enc_err, Encoder, false, enc_p_ctl));
+
+    (enc_writer, error_encoder_thread)
+}
+
+/// Replace failed encoder with a new one, returns false when the playout should stop.
+fn restart_encoder(
+    config: &PlayoutConfig,
+    ff_log_format: &str,
+    proc_control: &ProcessControl,
+    enc_writer: &mut BufWriter<ChildStdin>,
+    error_encoder_thread: &mut JoinHandle<Result<(), Error>>,
+) -> bool {
+    if proc_control.is_terminated.load(Ordering::SeqCst) {
+        return false;
+    }
+
+    if let Err(e) = proc_control.kill(Encoder) {
+        if !e.contains("exited process") {
+            error!("{e}")
+        }
+    }
+
+    if let Err(e) = proc_control.wait(Encoder) {
+        error!("{e}")
+    }
+
+    // encoder has ended regular, for example the desktop window was closed
+    let ended = proc_control
+        .encoder_term
+        .lock()
+        .unwrap()
+        .as_mut()
+        .and_then(|p| p.try_wait().ok().flatten())
+        .map_or(false, |status| status.success());
+
+    if ended || !proc_control.failed(Encoder, "encoder has stopped") {
+        return false;
+    }
+
+    proc_control.restart(Encoder);
+
+    let (new_writer, new_thread) = start_encoder(config, ff_log_format, proc_control);
+    let old_thread = std::mem::replace(error_encoder_thread, new_thread);
+    *enc_writer = new_writer;
+
+    if let Err(e) = old_thread.join() {
+        error!("{e:?}");
+    }
+
+    true
+}
+
+/// Source from the list item at `index`.
+pub fn list_source(play_control: &PlayerControl, index: usize) -> Option<String> {
+    play_control
+        .current_list
+        .lock()
+        .unwrap()
+        .get(index)
+        .map(|n| n.source.clone())
+}
+
+/// Pre-rolled decoder is only valid, when nobody has moved the playlist position in meantime.
+///
+/// Fillers and inserted clips play between list items, so the clip itself can differ
+/// from the list item. Instead the list item from pre-roll time (`list_source`) is compared.
+pub fn pre_roll_valid(
+    play_control: &PlayerControl,
+    playlist_init: &AtomicBool,
+    index: usize,
+    source: &Option<String>,
+) -> bool {
+    !playlist_init.load(Ordering::SeqCst)
+        && play_control.index.load(Ordering::SeqCst) == index
+        && &list_source(play_control, index) == source
+}
+
+/// Next clip from source, which should be processed.
+///
+/// Returns the clip together with its playlist index, or None when source has no more clips.
+fn next_clip(
+    get_source: &mut dyn Iterator<Item = Media>,
+    play_control: &PlayerControl,
+) -> Option<(Media, usize)> {
+    for node in get_source {
+        node.cmd.as_ref()?;
+
+        if node.process.unwrap() {
+            let index = play_control.index.load(Ordering::SeqCst).saturating_sub(1);
+
+            return Some((node, index));
+        }
+    }
+
+    None
+}
 
 /// Player
 ///
@@ -32,6 +328,15 @@ use ffplayout_lib::vec_strings;
 /// for getting live feeds.
 /// When a live ingest arrive, it stops the current playing and switch to the live source.
 /// When ingest stops, it switch back to playlist/folder mode.
+///
+/// With pre-roll, the decoder from the next clip starts some seconds before the current clip ends.
+/// The playlist index is set back until the switch, so RPC commands still see the right position.
+///
+/// Live items from playlist are played from [LiveEvent], which fills drop outs with filler.
+/// While the playout is on hold, the slate is played and the source is not touched.
+///
+/// Failed encoder and decoder get restarted from the supervisor in [ProcessControl],
+/// in playlist mode the playout goes on at the current position.
 pub fn player(
     config: &PlayoutConfig,
     play_control: PlayerControl,
@@ -40,12 +345,18 @@ pub fn player(
 ) {
     let config_clone = config.clone();
     let ff_log_format = format!("level+{}", config.logging.ffmpeg_level.to_lowercase());
-    let mut buffer = [0; 65088];
+    let preroll = config.processing.preroll;
     let mut live_on = false;
     let playlist_init = playout_stat.list_init.clone();
+    let mut ad_break = AdBreak::new(config, proc_control.is_terminated.clone());
+    // pre-rolled decoder, Some(None) when the source has no more clips
+    let mut next_unit: Option<Option<DecoderUnit>> = None;
+    let mut live_event: Option<LiveEvent> = None;
+    let mut live_next: Option<Media> = None;
+    let chain = playout_stat.chain.clone();
 
     // get source iterator
-    let get_source = source_generator(
+    let mut get_source = source_generator(
         config.clone(),
         play_control.current_list.clone(),
         play_control.index.clone(),
@@ -54,21 +365,8 @@ pub fn player(
     );
 
     // get ffmpeg output instance
-    let mut enc_proc = match config.out.mode {
-        Desktop => desktop::output(config, &ff_log_format),
-        Null => null::output(config, &ff_log_format),
-        Stream => stream::output(config, &ff_log_format),
-        _ => panic!("Output mode doesn't exists!"),
-    };
-
-    let mut enc_writer = BufWriter::new(enc_proc.stdin.take().unwrap());
-    let enc_err = BufReader::new(enc_proc.stderr.take().unwrap());
-
-    *proc_control.encoder_term.lock().unwrap() = Some(enc_proc);
-    let enc_p_ctl = proc_control.clone();
-
-    // spawn a thread to log ffmpeg output error messages
-    let error_encoder_thread = thread::spawn(move || stderr_reader(enc_err, Encoder, enc_p_ctl));
+    let (mut enc_writer, mut error_encoder_thread) =
+        start_encoder(config, &ff_log_format, &proc_control);
 
     let proc_control_c = proc_control.clone();
     let mut ingest_receiver = None;
@@ -80,65 +378,64 @@ pub fn player(
         thread::spawn(move || ingest_server(config_clone, ingest_sender, proc_control_c));
     }
 
-    'source_iter: for node in get_source {
-        *play_control.current_media.lock().unwrap() = Some(node.clone());
+    'source_iter: loop {
+        let hold = play_control.hold.lock().unwrap().is_some();
+
+        let mut dec_unit = match live_next.take() {
+            _ if hold => {
+                if let Some(Some(unit)) = next_unit.take() {
+                    unit.discard();
+                }
 
-        let mut cmd = match node.cmd {
-            Some(cmd) => cmd,
-            None => break,
+                let node = slate_node(config, &chain);
+                DecoderUnit::new(config, node, 0, &ff_log_format, &proc_control)
+            }
+            // reconnect or filler from running live event
+            Some(node) => {
+                let index = node.index.unwrap_or_default();
+                DecoderUnit::new(config, node, index, &ff_log_format, &proc_control)
+            }
+            None => match next_unit.take() {
+                Some(Some(unit)) if unit.is_valid(&play_control, &playlist_init) => {
+                    play_control.index.fetch_add(1, Ordering::SeqCst);
+                    unit
+                }
+                Some(None) => break,
+                pending => {
+                    if let Some(Some(unit)) = pending {
+                        unit.discard();
+                    }
+
+                    match next_clip(&mut get_source, &play_control) {
+                        Some((node, index)) => {
+                            DecoderUnit::new(config, node, index, &ff_log_format, &proc_control)
+                        }
+                        None => break,
+                    }
+                }
+            },
         };
 
-        if !node.process.unwrap() {
-            continue;
+        let node = dec_unit.node.clone();
+        let clip_length = node.out - node.seek;
+        let clip_start = Instant::now();
+        let mut on_air_since = None;
+
+        if node.is_live() && live_event.is_none() {
+            live_event = Some(LiveEvent::new(config, &node, &chain));
         }
 
+        *play_control.current_media.lock().unwrap() = Some(node.clone());
+        *proc_control.decoder_term.lock().unwrap() = dec_unit.proc.take();
+        ad_break.clip_start(&node, &play_control);
+
         info!(
             "Play for <yellow>{}</>: <b><magenta>{}  {}</></b>",
-            sec_to_time(node.out - node.seek),
+            sec_to_time(clip_length),
             node.source,
             node.audio
         );
 
-        let mut dec_cmd = vec_strings!["-hide_banner", "-nostats", "-v", &ff_log_format];
-        dec_cmd.append(&mut cmd);
-
-        if let Some(mut filter) = node.filter {
-            dec_cmd.append(&mut filter.cmd());
-            dec_cmd.append(&mut filter.map());
-        }
-
-        if let Some(mut cmd) = config.processing.cmd.clone() {
-            dec_cmd.append(&mut cmd);
-        }
-
-        debug!(
-            "Decoder CMD: <bright-blue>\"ffmpeg {}\"</>",
-            dec_cmd.join(" ")
-        );
-
-        // create ffmpeg decoder instance, for reading the input files
-        let mut dec_proc = match Command::new("ffmpeg")
-            .args(dec_cmd)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-        {
-            Ok(proc) => proc,
-            Err(e) => {
-                error!("couldn't spawn decoder process: {}", e);
-                panic!("couldn't spawn decoder process: {}", e)
-            }
-        };
-
-        let mut dec_reader = BufReader::new(dec_proc.stdout.take().unwrap());
-        let dec_err = BufReader::new(dec_proc.stderr.take().unwrap());
-
-        *proc_control.decoder_term.lock().unwrap() = Some(dec_proc);
-        let dec_p_ctl = proc_control.clone();
-
-        let error_decoder_thread =
-            thread::spawn(move || stderr_reader(dec_err, Decoder, dec_p_ctl));
-
         loop {
             // when server is running, read from channel
             if proc_control.server_is_running.load(Ordering::SeqCst) {
@@ -153,6 +450,10 @@ pub fn player(
                         error!("{e}")
                     }
 
+                    if let Some(Some(unit)) = next_unit.take() {
+                        unit.discard();
+                    }
+
                     live_on = true;
                     playlist_init.store(true, Ordering::SeqCst);
                 }
@@ -161,7 +462,15 @@ pub fn player(
                     if let Err(e) = enc_writer.write(&rx.1[..rx.0]) {
                         error!("Encoder write error: {:?}", e);
 
-                        break 'source_iter;
+                        if !restart_encoder(
+                            config,
+                            &ff_log_format,
+                            &proc_control,
+                            &mut enc_writer,
+                            &mut error_encoder_thread,
+                        ) {
+                            break 'source_iter;
+                        }
                     };
                 }
             // read from decoder instance
@@ -174,25 +483,78 @@ pub fn player(
                     }
 
                     live_on = false;
+
+                    // decoder was killed by the switch, go on with next clip
+                    break;
                 }
 
-                let dec_bytes_len = match dec_reader.read(&mut buffer[..]) {
-                    Ok(length) => length,
-                    Err(e) => {
-                        error!("Reading error from decoder: {e:?}");
+                let remaining = clip_length - clip_start.elapsed().as_secs_f64() * clock().speed();
+
+                // start decoder from next clip, before the current one ends
+                if preroll > 0.0
+                    && next_unit.is_none()
+                    && remaining <= preroll
+                    && live_event
+                        .as_ref()
+                        .map_or(true, |e| e.remaining() <= preroll)
+                    && play_control.hold.lock().unwrap().is_none()
+                    && !playlist_init.load(Ordering::SeqCst)
+                {
+                    next_unit = Some(
+                        with_look_ahead(remaining.max(0.0), || {
+                            next_clip(&mut get_source, &play_control)
+                        })
+                        .map(|(node, index)| {
+                            debug!("Pre-roll next clip: <b><magenta>{}</></b>", node.source);
+                            play_control.index.fetch_sub(1, Ordering::SeqCst);
+
+                            let mut unit = DecoderUnit::new(
+                                config,
+                                node,
+                                index,
+                                &ff_log_format,
+                                &proc_control,
+                            );
+                            unit.list_source = list_source(&play_control, index);
+
+                            unit
+                        }),
+                    );
+                }
 
-                        break 'source_iter;
+                match dec_unit.receiver.recv() {
+                    Ok((dec_bytes_len, buffer)) => {
+                        if node.is_live() && on_air_since.is_none() {
+                            info!("Live stream is on air: <b><magenta>{}</></b>", node.source);
+                            on_air_since = Some(Instant::now());
+                        }
+
+                        if let Err(e) = enc_writer.write(&buffer[..dec_bytes_len]) {
+                            error!("Encoder write error: {e:?}");
+
+                            if !restart_encoder(
+                                config,
+                                &ff_log_format,
+                                &proc_control,
+                                &mut enc_writer,
+                                &mut error_encoder_thread,
+                            ) {
+                                break 'source_iter;
+                            }
+
+                            // clip is lost in the old encoder, play on at the current position
+                            if config.processing.mode == Playlist {
+                                playlist_init.store(true, Ordering::SeqCst);
+
+                                if let Err(e) = proc_control.kill(Decoder) {
+                                    error!("{e}")
+                                }
+
+                                break;
+                            }
+                        };
                     }
-                };
-
-                if dec_bytes_len > 0 {
-                    if let Err(e) = enc_writer.write(&buffer[..dec_bytes_len]) {
-                        error!("Encoder write error: {e:?}");
-
-                        break 'source_iter;
-                    };
-                } else {
-                    break;
+                    Err(_) => break,
                 }
             }
         }
@@ -201,9 +563,30 @@ pub fn player(
             error!("{e}")
         }
 
-        if let Err(e) = error_decoder_thread.join() {
-            error!("{e:?}");
-        };
+        // failed decoder, play on at the current position after the restart delay
+        if proc_control.restart(Decoder) && config.processing.mode == Playlist {
+            playlist_init.store(true, Ordering::SeqCst);
+        }
+
+        if let Some(event) = live_event.as_mut() {
+            let on_air = on_air_since.map_or(0.0, |t| t.elapsed().as_secs_f64() * clock().speed());
+            let stopped = is_killed(&proc_control.decoder_term)
+                || proc_control.is_terminated.load(Ordering::SeqCst);
+
+            live_next = event.next_node(&node, on_air, stopped);
+
+            if live_next.is_none() {
+                live_event = None;
+            }
+        }
+
+        if !dec_unit.finish() {
+            break;
+        }
+    }
+
+    if let Some(Some(unit)) = next_unit {
+        unit.discard();
     }
 
     sleep(Duration::from_secs(1));
@@ -4,7 +4,7 @@ use simplelog::*;
 
 use crate::utils::prepare_output_cmd;
 use ffplayout_lib::{
-    utils::{Media, PlayoutConfig, ProcessUnit::*},
+    utils::{clock, Media, PlayoutConfig, ProcessUnit::*},
     vec_strings,
 };
 
@@ -16,15 +16,15 @@ pub fn output(config: &PlayoutConfig, log_format: &str) -> process::Child {
     media.unit = Encoder;
     media.add_filter(config, &None);
 
-    let enc_prefix = vec_strings![
-        "-hide_banner",
-        "-nostats",
-        "-v",
-        log_format,
-        "-re",
-        "-i",
-        "pipe:0"
-    ];
+    let mut enc_prefix = vec_strings!["-hide_banner", "-nostats", "-v", log_format];
+
+    // read input as fast as the playout clock runs
+    match clock().speed() {
+        speed if speed != 1.0 => enc_prefix.append(&mut vec_strings!["-readrate", speed]),
+        _ => enc_prefix.push("-re".to_string()),
+    }
+
+    enc_prefix.append(&mut vec_strings!["-i", "pipe:0"]);
 
     let enc_cmd = prepare_output_cmd(config, enc_prefix, &media.filter);
 
@@ -13,8 +13,9 @@ use serde_json::{json, Map};
 use simplelog::*;
 
 use ffplayout_lib::utils::{
-    get_delta, get_sec, sec_to_time, write_status, Ingest, Media, OutputMode::*, PlayerControl,
-    PlayoutConfig, PlayoutStatus, ProcessControl,
+    day_offset, day_sec, get_delta, get_sec, is_time_str, sec_to_time, time_now, time_to_sec,
+    valid_source, write_status, Decoder, Encoder, Ingest, Media, OutputMode::*, PlayerControl,
+    PlayoutConfig, PlayoutStatus, ProcessControl, ProcessMode, ProcessUnit,
 };
 
 use zmq_cmd::zmq_send;
@@ -111,6 +112,13 @@ fn get_media_map(media: Media) -> Value {
         "duration": media.duration,
         "category": media.category,
         "source": media.source,
+        "title": media.title,
+        "subtitle": media.subtitle,
+        "description": media.description,
+        "episode": media.episode,
+        "season": media.season,
+        "external_id": media.external_id,
+        "metadata": media.metadata,
     })
 }
 
@@ -137,6 +145,93 @@ fn get_data_map(config: &PlayoutConfig, media: Media) -> Map<String, Value> {
     data_map
 }
 
+/// Convert wall time (HH:MM:SS) from the current playlist, to playlist time.
+pub fn playlist_time(config: &PlayoutConfig, date: &str, time: &str) -> Option<f64> {
+    if !is_time_str(time) {
+        return None;
+    }
+
+    let mut target = day_sec(config, date, time_to_sec(time));
+
+    if target < config.playlist.start_sec.unwrap_or(0.0) {
+        target += day_offset(config);
+    }
+
+    Some(target)
+}
+
+/// Begin from the clip with the given index, for jumping to it.
+pub fn index_time(list: &[Media], index: usize) -> Option<f64> {
+    list.get(index).and_then(|m| m.begin)
+}
+
+/// Playlist time from a position in the clip, when it is inside the clip.
+pub fn position_time(clip: &Media, position: f64) -> Option<f64> {
+    match position >= 0.0 && position < clip.out - clip.seek {
+        true => clip.begin.map(|b| b + position),
+        false => None,
+    }
+}
+
+/// Find clip from playlist, which is on air at the given playlist time.
+pub fn clip_at(list: &[Media], time: f64) -> Option<Media> {
+    list.iter()
+        .find(|m| {
+            m.begin
+                .map_or(false, |b| b <= time && time < b + m.out - m.seek)
+        })
+        .cloned()
+}
+
+/// Process which plays the clip, in HLS mode the encoder reads the clips direct.
+fn clip_unit(config: &PlayoutConfig) -> ProcessUnit {
+    match config.out.mode {
+        HLS => Encoder,
+        _ => Decoder,
+    }
+}
+
+/// Move playout to the given playlist time.
+///
+/// The time shift is set, so that `target` is on air now, and the running clip gets stopped.
+/// Then the playlist initialize the clip at this time, with the right seek position.
+/// Returns the time shift, or None when no clip is running.
+pub fn move_to(
+    config: &PlayoutConfig,
+    proc: &ProcessControl,
+    playout_stat: &PlayoutStatus,
+    time_shift: &mut f64,
+    date: &mut String,
+    current_date: &str,
+    target: f64,
+) -> Option<f64> {
+    let unit = clip_unit(config);
+
+    if !proc.is_running(unit) {
+        return None;
+    }
+
+    let (delta, _) = get_delta(config, &target);
+
+    *time_shift = delta;
+    *date = current_date.to_string();
+    write_status(config, current_date, delta);
+    playout_stat.list_init.store(true, Ordering::SeqCst);
+
+    if let Err(e) = proc.kill(unit) {
+        error!("{e}")
+    };
+
+    Some(delta)
+}
+
+/// Stop the running clip, so that the player takes the next one.
+fn stop_clip(config: &PlayoutConfig, proc: &ProcessControl) {
+    if let Err(e) = proc.kill(clip_unit(config)) {
+        error!("{e}")
+    }
+}
+
 /// JSON RPC Server
 ///
 /// A simple rpc server for getting status information and controlling player:
@@ -145,6 +240,9 @@ fn get_data_map(config: &PlayoutConfig, media: Media) -> Map<String, Value> {
 /// - jump to next clip
 /// - get last clip
 /// - reset player state to original clip
+/// - insert clip, which plays immediately
+/// - jump to playlist index, seek to time in playlist, or seek inside current clip
+/// - hold playout on slate and release it
 pub fn json_rpc_server(
     config: PlayoutConfig,
     play_control: PlayerControl,
@@ -310,10 +408,218 @@ pub fn json_rpc_server(
                 return Ok(Value::String("Reset playout state failed".to_string()));
             }
 
+            // play clip immediately, afterwards playlist goes on in sync,
+            // or in shift mode the rest of the playlist gets delayed by the clip length
+            if map.contains_key("control") && &map["control"] == "insert" {
+                let source = map
+                    .get("source")
+                    .and_then(|s| s.as_str())
+                    .unwrap_or_default();
+                let shift_mode = map.get("mode").and_then(|m| m.as_str()) == Some("shift");
+                let index = play_control.index.load(Ordering::SeqCst);
+
+                if !valid_source(source) {
+                    return Ok(Value::String(format!("Insert source not valid: {source}")));
+                }
+
+                let mut media = Media::new(index, source, true);
+
+                if media.duration <= 0.0 {
+                    return Ok(Value::String(format!("Insert source not valid: {source}")));
+                }
+
+                media.out = media.duration;
+                media.begin = Some(get_sec());
+
+                let unit = clip_unit(&config);
+                // the player waits on this lock for the next clip, until the insert is set
+                let mut insert = playout_stat.insert.lock().unwrap();
+
+                if proc.is_running(unit) && proc.kill(unit).is_ok() {
+                    let mut data_map = Map::new();
+                    let mut shift = 0.0;
+
+                    if shift_mode && config.processing.mode == ProcessMode::Playlist {
+                        if *date == current_date {
+                            shift = *time_shift;
+                        }
+
+                        shift -= media.duration;
+                        *time_shift = shift;
+                        *date = current_date.clone();
+                        write_status(&config, &current_date, shift);
+                    }
+
+                    *insert = Some(media.clone());
+                    // after the inserted clip, playlist seeks to the current (shifted) time
+                    playout_stat.list_init.store(true, Ordering::SeqCst);
+                    drop(insert);
+
+                    info!(
+                        "Insert clip with <yellow>{:.3}</> seconds: <b><magenta>{source}</></b>",
+                        media.duration
+                    );
+
+                    let operation = match shift_mode {
+                        true => "insert_and_shift",
+                        false => "insert_in_sync",
+                    };
+
+                    data_map.insert("operation".to_string(), json!(operation));
+                    data_map.insert("shifted_seconds".to_string(), json!(shift));
+                    data_map.insert("media".to_string(), get_media_map(media));
+
+                    return Ok(Value::Object(data_map));
+                }
+
+                return Ok(Value::String("Insert failed".to_string()));
+            }
+
+            // jump to index, seek to wall time in playlist, or seek in current clip
+            if map.contains_key("control")
+                && ["jump", "seek_time", "seek"].contains(&map["control"].as_str().unwrap_or(""))
+            {
+                if config.processing.mode != ProcessMode::Playlist {
+                    return Ok(Value::String("Only possible in playlist mode".to_string()));
+                }
+
+                let control = map["control"].as_str().unwrap_or_default();
+                let target = match control {
+                    "jump" => map
+                        .get("index")
+                        .and_then(|i| i.as_u64())
+                        .and_then(|i| index_time(&current_list, i as usize)),
+                    "seek_time" => map
+                        .get("time")
+                        .and_then(|t| t.as_str())
+                        .and_then(|t| playlist_time(&config, &current_date, t)),
+                    _ => {
+                        let position = map.get("position").and_then(|p| p.as_f64());
+                        let clip = play_control
+                            .current_media
+                            .lock()
+                            .unwrap()
+                            .as_ref()
+                            .and_then(|m| m.index)
+                            .and_then(|i| current_list.get(i).cloned());
+
+                        match (clip, position) {
+                            (Some(clip), Some(pos)) => position_time(&clip, pos),
+                            _ => None,
+                        }
+                    }
+                };
+
+                let (target, media) =
+                    match target.and_then(|t| clip_at(&current_list, t).map(|m| (t, m))) {
+                        Some(found) => found,
+                        None => return Ok(Value::String("Target not in playlist".to_string())),
+                    };
+
+                if let Some(delta) = move_to(
+                    &config,
+                    &proc,
+                    &playout_stat,
+                    &mut time_shift,
+                    &mut date,
+                    &current_date,
+                    target,
+                ) {
+                    let mut data_map = Map::new();
+                    let operation = match control {
+                        "jump" => "jump_to_index",
+                        "seek_time" => "seek_to_time",
+                        _ => "seek_in_clip",
+                    };
+
+                    info!(
+                        "Move to <yellow>{}</> in playlist: <b><magenta>{}</></b>",
+                        sec_to_time(target),
+                        media.source
+                    );
+
+                    data_map.insert("operation".to_string(), json!(operation));
+                    data_map.insert("shifted_seconds".to_string(), json!(delta));
+                    data_map.insert("media".to_string(), get_media_map(media));
+
+                    return Ok(Value::Object(data_map));
+                }
+
+                return Ok(Value::String("Move failed".to_string()));
+            }
+
+            // hold playout, the slate plays and the encoder keeps running
+            if map.contains_key("control") && &map["control"] == "hold" {
+                let mut hold = play_control.hold.lock().unwrap();
+
+                if hold.is_some() {
+                    return Ok(Value::String("Playout is already on hold".to_string()));
+                }
+
+                *hold = Some(time_now());
+                drop(hold);
+
+                info!("Hold playout");
+                stop_clip(&config, &proc);
+
+                let mut data_map = Map::new();
+                data_map.insert("operation".to_string(), json!("hold"));
+
+                return Ok(Value::Object(data_map));
+            }
+
+            // release hold, playlist goes on in sync, or in resume mode from where it was hold
+            if map.contains_key("control") && &map["control"] == "release" {
+                let start = match play_control.hold.lock().unwrap().take() {
+                    Some(start) => start,
+                    None => return Ok(Value::String("Playout is not on hold".to_string())),
+                };
+                let held = (time_now() - start).num_milliseconds() as f64 / 1000.0;
+                let resume = map.get("mode").and_then(|m| m.as_str()) == Some("resume");
+                let mut data_map = Map::new();
+
+                if config.processing.mode == ProcessMode::Playlist {
+                    if resume {
+                        let mut shift = 0.0;
+
+                        if *date == current_date {
+                            shift = *time_shift;
+                        }
+
+                        shift -= held;
+                        *time_shift = shift;
+                        *date = current_date.clone();
+                        write_status(&config, &current_date, shift);
+                    }
+
+                    playout_stat.list_init.store(true, Ordering::SeqCst);
+                }
+
+                info!(
+                    "Release playout, was on hold for <yellow>{}</>",
+                    sec_to_time(held)
+                );
+                stop_clip(&config, &proc);
+
+                let operation = match resume {
+                    true => "release_and_resume",
+                    false => "release_in_sync",
+                };
+
+                data_map.insert("operation".to_string(), json!(operation));
+                data_map.insert("hold_seconds".to_string(), json!(held));
+                data_map.insert("shifted_seconds".to_string(), json!(*time_shift));
+
+                return Ok(Value::Object(data_map));
+            }
+
             // get infos about current clip
             if map.contains_key("media") && &map["media"] == "current" {
                 if let Some(media) = play_control.current_media.lock().unwrap().clone() {
-                    let data_map = get_data_map(&config, media);
+                    let mut data_map = get_data_map(&config, media);
+                    let supervisor = proc.supervisor.lock().unwrap().clone();
+
+                    data_map.insert("supervisor".to_string(), json!(supervisor));
 
                     return Ok(Value::Object(data_map));
                 };
@@ -1,6 +1,8 @@
 use clap::Parser;
 
-use ffplayout_lib::utils::{OutputMode, ProcessMode};
+use ffplayout_lib::utils::{
+    export::ExportFormat, import::ImportFormat, ClockMode, OutputMode, ProcessMode,
+};
 
 #[derive(Parser, Debug, Clone)]
 #[clap(version,
@@ -36,16 +38,31 @@ pub struct Args {
     #[clap(
         short,
         long,
-        help = "Target date (YYYY-MM-DD) for text/m3u to playlist import"
+        help = "Target date (YYYY-MM-DD) for playlist import/export"
     )]
     pub date: Option<String>,
 
     #[clap(
         long,
-        help = "Import a given text/m3u file and create a playlist from it"
+        help = "Import a given text/m3u/csv/xspf/edl file and create a playlist from it"
     )]
     pub import: Option<String>,
 
+    #[clap(
+        long,
+        help = "Import format: text, m3u, csv, xspf, edl. Default is taken from file extension"
+    )]
+    pub import_format: Option<ImportFormat>,
+
+    #[clap(long, help = "Export playlist from given date to m3u/csv/edl file")]
+    pub export: Option<String>,
+
+    #[clap(
+        long,
+        help = "Export format: m3u, csv, edl. Default is taken from file extension"
+    )]
+    pub export_format: Option<ExportFormat>,
+
     #[clap(short, long, help = "Path from playlist")]
     pub playlist: Option<String>,
 
@@ -72,6 +89,28 @@ pub struct Args {
     #[clap(short, long, help = "Set audio volume")]
     pub volume: Option<f64>,
 
+    #[clap(long, help = "Clock source: system, offset, accelerated")]
+    pub clock: Option<ClockMode>,
+
+    #[clap(
+        long,
+        help = "Clock offset in seconds, negative values are behind system time",
+        allow_hyphen_values = true
+    )]
+    pub clock_offset: Option<f64>,
+
+    #[clap(
+        long,
+        help = "Speed from accelerated clock, like 60 for one hour per minute"
+    )]
+    pub clock_speed: Option<f64>,
+
+    #[clap(
+        long,
+        help = "Start time from accelerated clock, like: 2022-11-01T05:59:00"
+    )]
+    pub clock_start: Option<String>,
+
     #[cfg(debug_assertions)]
     #[clap(long, help = "fake date time, for debugging")]
     pub fake_time: Option<String>,
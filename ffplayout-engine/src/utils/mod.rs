@@ -87,6 +87,22 @@ pub fn get_config(args: Args) -> PlayoutConfig {
         }
     }
 
+    if let Some(clock) = args.clock {
+        config.general.clock = clock;
+    }
+
+    if let Some(offset) = args.clock_offset {
+        config.general.clock_offset = offset;
+    }
+
+    if let Some(speed) = args.clock_speed {
+        config.general.clock_speed = speed;
+    }
+
+    if let Some(start) = args.clock_start {
+        config.general.clock_start = start;
+    }
+
     if let Some(volume) = args.volume {
         config.processing.volume = volume;
     }
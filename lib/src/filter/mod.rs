@@ -12,8 +12,8 @@ mod custom;
 pub mod v_drawtext;
 
 use crate::utils::{
-    controller::ProcessUnit::*, fps_calc, get_delta, is_close, Media, MediaProbe, OutputMode::*,
-    PlayoutConfig,
+    controller::ProcessUnit::*, fps_calc, get_delta, is_close, FrameRate, FrameTime, Media,
+    MediaProbe, OutputMode::*, PlayoutConfig,
 };
 
 use super::vec_strings;
@@ -118,6 +118,39 @@ impl Filters {
         }
     }
 
+    /// Merge a stream from another input into the chain from `track_nr`.
+    ///
+    /// The stream gets the `filter`, then `merge` takes it as first
+    /// and the current chain as second input.
+    pub fn add_merge(
+        &mut self,
+        filter: &str,
+        merge: &str,
+        input: i32,
+        track_nr: i32,
+        filter_type: FilterType,
+    ) {
+        let (last, null) = match filter_type {
+            Audio => (self.audio_last, "anull"),
+            Video => (self.video_last, "null"),
+        };
+
+        if last != track_nr {
+            self.add_filter(null, track_nr, filter_type);
+        }
+
+        let main = format!("[{filter_type}main{track_nr}]");
+        let tail = format!("[{filter_type}tail{track_nr}]");
+        let chain = match filter_type {
+            Audio => &mut self.audio_chain,
+            Video => &mut self.video_chain,
+        };
+
+        chain.push_str(&format!(
+            "{main};[{input}:{filter_type}:{track_nr}]{filter}{tail};{tail}{main}{merge}"
+        ));
+    }
+
     pub fn cmd(&mut self) -> Vec<String> {
         if !self.output_chain.is_empty() {
             return self.output_chain.clone();
@@ -263,26 +296,68 @@ fn scale(
     }
 }
 
-fn fade(node: &mut Media, chain: &mut Filters, nr: i32, filter_type: FilterType) {
+fn fade(node: &mut Media, chain: &mut Filters, nr: i32, filter_type: FilterType, rate: FrameRate) {
     let mut t = "";
+    let length = node.frame_length(rate);
+    let fade_out = FrameTime::from_sec(1.0, rate);
 
     if filter_type == Audio {
         t = "a"
     }
 
-    if node.seek > 0.0 || node.unit == Ingest {
+    if (node.seek > 0.0 || node.unit == Ingest) && node.overlap.is_none() {
         chain.add_filter(&format!("{t}fade=in:st=0:d=0.5"), nr, filter_type)
     }
 
-    if node.out != node.duration && node.out - node.seek > 1.0 {
+    if node.out != node.duration && length > fade_out && node.next_overlap != Some(true) {
         chain.add_filter(
-            &format!("{t}fade=out:st={}:d=1.0", (node.out - node.seek - 1.0)),
+            &format!("{t}fade=out:st={}:d=1.0", length - fade_out),
             nr,
             filter_type,
         )
     }
 }
 
+/// Blend the tail from the previous clip (second input) into the begin of the clip.
+///
+/// The tail is as long as the transition, so the output keeps the length from the clip.
+fn transition(node: &Media, chain: &mut Filters, config: &PlayoutConfig) {
+    if let Some(overlap) = &node.overlap {
+        let format = format!("fps={},format=yuv420p,setsar=1", config.processing.fps);
+        let (w, h) = (config.processing.width, config.processing.height);
+
+        chain.add_filter(&format, 0, Video);
+        chain.add_merge(
+            &format!(
+                "scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,{format}"
+            ),
+            &format!(
+                "xfade=transition={}:duration={}:offset=0",
+                overlap.kind, overlap.duration
+            ),
+            1,
+            0,
+            Video,
+        );
+    }
+}
+
+fn audio_transition(node: &Media, chain: &mut Filters, nr: i32) {
+    if let Some(overlap) = node
+        .overlap
+        .as_ref()
+        .filter(|o| o.audio_streams.map_or(false, |s| (nr as usize) < s))
+    {
+        chain.add_merge(
+            "anull",
+            &format!("acrossfade=d={}", overlap.duration),
+            1,
+            nr,
+            Audio,
+        );
+    }
+}
+
 fn overlay(node: &mut Media, chain: &mut Filters, config: &PlayoutConfig) {
     if config.processing.add_logo
         && Path::new(&config.processing.logo).is_file()
@@ -305,9 +380,11 @@ fn overlay(node: &mut Media, chain: &mut Filters, config: &PlayoutConfig) {
         }
 
         if node.next_ad.unwrap_or(false) {
+            let rate = FrameRate::from_fps(config.processing.fps);
+
             logo_chain.push_str(&format!(
                 ",fade=out:st={}:d=1.0:alpha=1",
-                node.out - node.seek - 1.0
+                node.frame_length(rate) - FrameTime::from_sec(1.0, rate)
             ))
         }
 
@@ -315,7 +392,7 @@ fn overlay(node: &mut Media, chain: &mut Filters, config: &PlayoutConfig) {
     }
 }
 
-fn extend_video(node: &mut Media, chain: &mut Filters) {
+fn extend_video(node: &mut Media, chain: &mut Filters, rate: FrameRate) {
     if let Some(video_duration) = node
         .probe
         .as_ref()
@@ -327,7 +404,7 @@ fn extend_video(node: &mut Media, chain: &mut Filters) {
             chain.add_filter(
                 &format!(
                     "tpad=stop_mode=add:stop_duration={}",
-                    (node.out - node.seek) - (video_duration - node.seek)
+                    node.frame_length(rate) - FrameTime::from_sec(video_duration - node.seek, rate)
                 ),
                 0,
                 Video,
@@ -352,15 +429,15 @@ fn add_text(
     }
 }
 
-fn add_audio(node: &Media, chain: &mut Filters, nr: i32) {
+fn add_audio(node: &Media, chain: &mut Filters, nr: i32, rate: FrameRate) {
     let audio = format!(
         "aevalsrc=0:channel_layout=stereo:duration={}:sample_rate=48000",
-        node.out - node.seek
+        node.frame_length(rate)
     );
     chain.add_filter(&audio, nr, Audio);
 }
 
-fn extend_audio(node: &mut Media, chain: &mut Filters, nr: i32) {
+fn extend_audio(node: &mut Media, chain: &mut Filters, nr: i32, rate: FrameRate) {
     let probe = if Path::new(&node.audio).is_file() {
         Some(MediaProbe::new(&node.audio))
     } else {
@@ -375,7 +452,7 @@ fn extend_audio(node: &mut Media, chain: &mut Filters, nr: i32) {
     {
         if node.out - node.seek > audio_duration - node.seek + 0.1 && node.duration >= node.out {
             chain.add_filter(
-                &format!("apad=whole_dur={}", node.out - node.seek),
+                &format!("apad=whole_dur={}", node.frame_length(rate)),
                 nr,
                 Audio,
             )
@@ -502,6 +579,8 @@ pub fn filter_chains(
     filter_chain: &Option<Arc<Mutex<Vec<String>>>>,
 ) -> Filters {
     let mut filters = Filters::new(config.processing.audio_tracks, 0);
+    // lengths in filters are counted in frames, so cuts are on frame boundaries
+    let rate = FrameRate::from_fps(config.processing.fps);
 
     if node.unit == Encoder {
         add_text(node, &mut filters, config, filter_chain);
@@ -536,14 +615,15 @@ pub fn filter_chains(
             );
         }
 
-        extend_video(node, &mut filters);
+        extend_video(node, &mut filters, rate);
+        transition(node, &mut filters, config);
     } else {
         fps(0.0, &mut filters, config);
         scale(None, None, 1.0, &mut filters, config);
     }
 
     add_text(node, &mut filters, config, filter_chain);
-    fade(node, &mut filters, 0, Video);
+    fade(node, &mut filters, 0, Video, rate);
     overlay(node, &mut filters, config);
     realtime(node, &mut filters, config);
 
@@ -560,22 +640,25 @@ pub fn filter_chains(
             .and_then(|p| p.audio_streams.get(i as usize))
             .is_some()
             || Path::new(&node.audio).is_file()
+            // live streams are not probed, expect that they have at least one audio track
+            || (node.is_live() && i == 0)
         {
-            extend_audio(node, &mut filters, i);
+            extend_audio(node, &mut filters, i, rate);
         } else if node.unit == Decoder {
             warn!(
                 "Missing audio track (id {i}) from <b><magenta>{}</></b>",
                 node.source
             );
-            add_audio(node, &mut filters, i);
+            add_audio(node, &mut filters, i, rate);
         }
 
         // add at least anull filter, for correct filter construction,
         // is important for split filter in HLS mode
         filters.add_filter("anull", i, Audio);
+        audio_transition(node, &mut filters, i);
 
         add_loudnorm(node, &mut filters, config, i);
-        fade(node, &mut filters, i, Audio);
+        fade(node, &mut filters, i, Audio, rate);
         audio_volume(&mut filters, config, i);
 
         custom(&proc_af, &mut filters, i, Audio);
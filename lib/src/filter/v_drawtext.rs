@@ -8,6 +8,30 @@ use regex::Regex;
 
 use crate::utils::{controller::ProcessUnit::*, Media, PlayoutConfig};
 
+/// Fill text template with metadata from clip.
+fn fill_template(template: &str, node: &Media, filename: &str) -> String {
+    let mut text = template
+        .replace("{filename}", filename)
+        .replace("{title}", &node.title)
+        .replace("{subtitle}", &node.subtitle)
+        .replace("{description}", &node.description)
+        .replace(
+            "{episode}",
+            &node.episode.map(|e| e.to_string()).unwrap_or_default(),
+        )
+        .replace(
+            "{season}",
+            &node.season.map(|s| s.to_string()).unwrap_or_default(),
+        )
+        .replace("{external_id}", &node.external_id);
+
+    for (key, value) in &node.metadata {
+        text = text.replace(&format!("{{{key}}}"), value);
+    }
+
+    text
+}
+
 pub fn filter_node(
     config: &PlayoutConfig,
     node: Option<&Media>,
@@ -27,8 +51,9 @@ pub fn filter_node(
 
     // TODO: in Rust 1.66 use let_chains instead
     if config.text.text_from_filename && node.is_some() {
-        let source = node.unwrap_or(&Media::new(0, "", false)).source.clone();
-        let text = match Regex::new(&config.text.regex)
+        let media = node.unwrap_or(&Media::new(0, "", false)).clone();
+        let source = media.source.clone();
+        let filename = match Regex::new(&config.text.regex)
             .ok()
             .and_then(|r| r.captures(&source))
         {
@@ -40,6 +65,14 @@ pub fn filter_node(
                 .to_string(),
         };
 
+        let text = if !config.text.template.is_empty() {
+            fill_template(&config.text.template, &media, &filename)
+        } else if !media.title.is_empty() {
+            media.title.clone()
+        } else {
+            filename
+        };
+
         let escaped_text = text
             .replace('\'', "'\\\\\\''")
             .replace('%', "\\\\\\%")
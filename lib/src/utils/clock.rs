@@ -0,0 +1,122 @@
+/// Clock Source
+///
+/// The playout time comes from a clock, which can be:
+///
+/// - `system`: the system clock.
+/// - `offset`: the system clock, shifted by a fixed number of seconds.
+///   For channels, which run delayed against another time zone.
+/// - `accelerated`: a simulated clock, which starts at a given time and runs `speed` times faster.
+///   With null output, the schedule from a whole day can be tested in minutes.
+use std::{cell::Cell, sync::Mutex};
+
+use chrono::{prelude::*, Duration};
+
+use crate::utils::{channel_instant, time_zone, ClockMode, PlayoutConfig};
+
+static CLOCK: Mutex<Clock> = Mutex::new(Clock::System);
+
+thread_local! {
+    static LOOK_AHEAD: Cell<f64> = Cell::new(0.0);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Clock {
+    System,
+    Offset(f64),
+    Accelerated {
+        start: DateTime<Utc>,
+        origin: DateTime<Utc>,
+        speed: f64,
+    },
+}
+
+impl Clock {
+    /// Accelerated clock, which starts now at the given time.
+    pub fn accelerated(start: DateTime<Utc>, speed: f64) -> Self {
+        Self::Accelerated {
+            start,
+            origin: Utc::now(),
+            speed,
+        }
+    }
+
+    pub fn from_config(config: &PlayoutConfig) -> Result<Self, String> {
+        match config.general.clock {
+            ClockMode::System => Ok(Self::System),
+            ClockMode::Offset => Ok(Self::Offset(config.general.clock_offset)),
+            ClockMode::Accelerated => {
+                if config.general.clock_speed <= 0.0 {
+                    return Err(format!(
+                        "Clock speed <yellow>{}</> is not valid, it must be greater then 0!",
+                        config.general.clock_speed
+                    ));
+                }
+
+                let start = match config.general.clock_start.trim() {
+                    "" => Utc::now(),
+                    time => match DateTime::parse_from_rfc3339(time) {
+                        Ok(t) => t.with_timezone(&Utc),
+                        Err(_) => NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M:%S")
+                            .map(|t| channel_instant(time_zone(), t))
+                            .map_err(|e| {
+                                format!("Clock start <b><magenta>{time}</></b> is not valid: {e}")
+                            })?,
+                    },
+                };
+
+                Ok(Self::accelerated(start, config.general.clock_speed))
+            }
+        }
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        match self {
+            Self::System => Utc::now(),
+            Self::Offset(offset) => Utc::now() + Duration::milliseconds((offset * 1000.0) as i64),
+            Self::Accelerated {
+                start,
+                origin,
+                speed,
+            } => {
+                let elapsed = (Utc::now() - *origin)
+                    .num_microseconds()
+                    .unwrap_or_default();
+
+                *start + Duration::microseconds((elapsed as f64 * speed) as i64)
+            }
+        }
+    }
+
+    /// How many times faster the clock runs than real time.
+    pub fn speed(&self) -> f64 {
+        match self {
+            Self::Accelerated { speed, .. } => *speed,
+            _ => 1.0,
+        }
+    }
+}
+
+/// Set the clock, which is used from the playout.
+pub fn set_clock(clock: Clock) {
+    *CLOCK.lock().unwrap() = clock;
+}
+
+pub fn clock() -> Clock {
+    *CLOCK.lock().unwrap()
+}
+
+/// Run `f` with the playout time shifted `sec` seconds into the future, only for this thread.
+///
+/// Used when the next clip gets prepared before the current one ends,
+/// so timing checks see the moment when the clip really starts.
+pub fn with_look_ahead<T>(sec: f64, f: impl FnOnce() -> T) -> T {
+    LOOK_AHEAD.with(|l| l.set(sec));
+    let result = f();
+    LOOK_AHEAD.with(|l| l.set(0.0));
+
+    result
+}
+
+pub fn look_ahead() -> Duration {
+    Duration::milliseconds((LOOK_AHEAD.with(|l| l.get()) * 1000.0) as i64)
+}
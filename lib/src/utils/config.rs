@@ -1,4 +1,5 @@
 use std::{
+    collections::BTreeMap,
     env, fmt,
     fs::File,
     path::{Path, PathBuf},
@@ -10,7 +11,7 @@ use serde::{Deserialize, Serialize};
 use shlex::split;
 
 use super::vec_strings;
-use crate::utils::{free_tcp_socket, home_dir, time_to_sec, OutputMode::*};
+use crate::utils::{free_tcp_socket, home_dir, time_to_sec, OutputMode::*, Transition};
 
 pub const DUMMY_LEN: f64 = 60.0;
 pub const IMAGE_FORMAT: [&str; 21] = [
@@ -83,6 +84,29 @@ impl FromStr for ProcessMode {
     }
 }
 
+/// Source from the playout time, see [`Clock`](crate::utils::Clock).
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClockMode {
+    #[default]
+    System,
+    Offset,
+    Accelerated,
+}
+
+impl FromStr for ClockMode {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "system" => Ok(Self::System),
+            "offset" => Ok(Self::Offset),
+            "accelerated" => Ok(Self::Accelerated),
+            _ => Err("Use 'system', 'offset' or 'accelerated'".to_string()),
+        }
+    }
+}
+
 /// Global Config
 ///
 /// This we init ones, when ffplayout is starting and use them globally in the hole program.
@@ -105,6 +129,27 @@ pub struct General {
     pub help_text: String,
     pub stop_threshold: f64,
 
+    #[serde(default)]
+    pub stop_on_desync: bool,
+
+    #[serde(default = "default_max_failures")]
+    pub max_failures: usize,
+
+    #[serde(default = "default_restart_delay")]
+    pub restart_delay: f64,
+
+    #[serde(default)]
+    pub clock: ClockMode,
+
+    #[serde(default)]
+    pub clock_offset: f64,
+
+    #[serde(default = "default_clock_speed")]
+    pub clock_speed: f64,
+
+    #[serde(default)]
+    pub clock_start: String,
+
     #[serde(skip_serializing, skip_deserializing)]
     pub generate: Option<Vec<String>>,
 
@@ -177,6 +222,10 @@ pub struct Processing {
     pub volume: f64,
     #[serde(default)]
     pub custom_filter: String,
+    #[serde(default)]
+    pub preroll: f64,
+    #[serde(default)]
+    pub transitions: BTreeMap<String, Transition>,
 
     #[serde(skip_serializing, skip_deserializing)]
     pub cmd: Option<Vec<String>>,
@@ -207,6 +256,21 @@ pub struct Playlist {
     pub length_sec: Option<f64>,
 
     pub infinit: bool,
+
+    #[serde(default)]
+    pub timezone: String,
+
+    #[serde(default)]
+    pub template: String,
+
+    #[serde(default)]
+    pub cache_path: String,
+
+    #[serde(default = "default_live_timeout")]
+    pub live_timeout: f64,
+
+    #[serde(default = "default_live_retry")]
+    pub live_retry: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -214,8 +278,36 @@ pub struct Storage {
     pub help_text: String,
     pub path: String,
     pub filler_clip: String,
+    #[serde(default)]
+    pub slate_clip: String,
     pub extensions: Vec<String>,
     pub shuffle: bool,
+    #[serde(default)]
+    pub no_repeat_count: usize,
+    #[serde(default)]
+    pub no_repeat_time: f64,
+    #[serde(default)]
+    pub weight_by_age: bool,
+    #[serde(default)]
+    pub weights: BTreeMap<String, f64>,
+    #[serde(default)]
+    pub rotation: Vec<RotationRule>,
+}
+
+/// Rotation rule for folder mode and the playlist generator.
+///
+/// `folder` is relative to the storage path. With `every` one clip from the folder is played
+/// after every N clips, with `hourly` one at the top of the hour. Otherwise the clips from
+/// the folder get `percent` from all other clips.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct RotationRule {
+    pub folder: String,
+    #[serde(default)]
+    pub every: usize,
+    #[serde(default)]
+    pub hourly: bool,
+    #[serde(default)]
+    pub percent: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -236,6 +328,8 @@ pub struct Text {
     pub text_from_filename: bool,
     pub style: String,
     pub regex: String,
+    #[serde(default)]
+    pub template: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -243,6 +337,12 @@ pub struct Out {
     pub help_text: String,
     pub mode: OutputMode,
     pub output_param: String,
+    #[serde(default)]
+    pub cue_markers: bool,
+    #[serde(default)]
+    pub scte35_address: String,
+    #[serde(default = "default_scte35_pid")]
+    pub scte35_pid: u16,
 
     #[serde(skip_serializing, skip_deserializing)]
     pub output_count: usize,
@@ -252,6 +352,30 @@ pub struct Out {
     pub output_cmd: Option<Vec<String>>,
 }
 
+fn default_clock_speed() -> f64 {
+    1.0
+}
+
+pub(crate) fn default_max_failures() -> usize {
+    5
+}
+
+pub(crate) fn default_restart_delay() -> f64 {
+    2.0
+}
+
+fn default_live_timeout() -> f64 {
+    10.0
+}
+
+fn default_live_retry() -> f64 {
+    5.0
+}
+
+fn default_scte35_pid() -> u16 {
+    500
+}
+
 fn default_tracks() -> i32 {
     1
 }
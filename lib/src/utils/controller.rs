@@ -5,13 +5,25 @@ use std::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc, Mutex,
     },
+    thread::sleep,
+    time::{Duration, Instant},
 };
 
+use chrono::{DateTime, Local};
 use jsonrpc_http_server::CloseHandle;
 use serde::{Deserialize, Serialize};
 use simplelog::*;
 
-use crate::utils::Media;
+use crate::utils::{
+    config::{default_max_failures, default_restart_delay},
+    time_now, Media,
+};
+
+/// Longest delay before a restart, in seconds.
+const MAX_RESTART_DELAY: f64 = 60.0;
+
+/// Processes must run this long without failure, before failures are counted from new.
+const STABLE_TIME: Duration = Duration::from_secs(300);
 
 /// Defined process units.
 #[derive(Clone, Debug, Copy, Eq, Serialize, Deserialize, PartialEq)]
@@ -39,10 +51,45 @@ impl Default for ProcessUnit {
 
 use ProcessUnit::*;
 
+/// Supervisor State
+///
+/// Failed processes get restarted, with a delay which doubles on every failure.
+/// When too many failures come in a row, the playout gives up.
+#[derive(Clone, Debug, Serialize)]
+pub struct Supervisor {
+    pub max_failures: usize,
+    pub delay: f64,
+    pub failures: usize,
+    pub restarts: usize,
+    pub last_failure: Option<String>,
+    pub last_restart: Option<DateTime<Local>>,
+    #[serde(skip)]
+    failed_at: Option<Instant>,
+    #[serde(skip)]
+    pending: Vec<ProcessUnit>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self {
+            max_failures: default_max_failures(),
+            delay: default_restart_delay(),
+            failures: 0,
+            restarts: 0,
+            last_failure: None,
+            last_restart: None,
+            failed_at: None,
+            pending: vec![],
+        }
+    }
+}
+
 /// Process Controller
 ///
 /// We save here some global states, about what is running and which processes are alive.
 /// This we need for process termination, skipping clip decoder etc.
+///
+/// `supervisor` counts failures from ffmpeg processes and decides, if they get restarted.
 #[derive(Clone)]
 pub struct ProcessControl {
     pub decoder_term: Arc<Mutex<Option<Child>>>,
@@ -52,6 +99,7 @@ pub struct ProcessControl {
     pub rpc_handle: Arc<Mutex<Option<CloseHandle>>>,
     pub is_terminated: Arc<AtomicBool>,
     pub is_alive: Arc<AtomicBool>,
+    pub supervisor: Arc<Mutex<Supervisor>>,
 }
 
 impl ProcessControl {
@@ -64,8 +112,92 @@ impl ProcessControl {
             rpc_handle: Arc::new(Mutex::new(None)),
             is_terminated: Arc::new(AtomicBool::new(false)),
             is_alive: Arc::new(AtomicBool::new(true)),
+            supervisor: Arc::new(Mutex::new(Supervisor::default())),
         }
     }
+
+    /// Set how many failures in a row are allowed, and the delay before the first restart.
+    pub fn supervise(&self, max_failures: usize, delay: f64) {
+        let mut supervisor = self.supervisor.lock().unwrap();
+
+        supervisor.max_failures = max_failures;
+        supervisor.delay = delay.max(0.0);
+    }
+
+    /// Register failed process, it gets restarted with [restart](Self::restart).
+    ///
+    /// Returns false, when there are too many failures in a row and the playout should stop.
+    pub fn failed(&self, unit: ProcessUnit, reason: &str) -> bool {
+        let mut supervisor = self.supervisor.lock().unwrap();
+
+        if self.is_terminated.load(Ordering::SeqCst) || supervisor.pending.contains(&unit) {
+            return true;
+        }
+
+        if supervisor
+            .failed_at
+            .map_or(false, |t| t.elapsed() > STABLE_TIME)
+        {
+            supervisor.failures = 0;
+        }
+
+        supervisor.failures += 1;
+        supervisor.failed_at = Some(Instant::now());
+        supervisor.last_failure = Some(format!("{unit}: {reason}"));
+
+        if supervisor.failures > supervisor.max_failures {
+            error!(
+                "{unit} failed <yellow>{}</> times in a row, give up!",
+                supervisor.failures
+            );
+
+            return false;
+        }
+
+        error!(
+            "{unit} failed (<yellow>{}/{}</>): {reason}",
+            supervisor.failures, supervisor.max_failures
+        );
+
+        supervisor.pending.push(unit);
+
+        true
+    }
+
+    /// Wait before the failed process gets started again.
+    ///
+    /// The decoder starts again without delay, because the encoder waits for its data.
+    /// Returns false, when the process has not failed.
+    pub fn restart(&self, unit: ProcessUnit) -> bool {
+        let delay = {
+            let mut supervisor = self.supervisor.lock().unwrap();
+
+            match supervisor.pending.iter().position(|u| *u == unit) {
+                Some(i) => supervisor.pending.remove(i),
+                None => return false,
+            };
+
+            supervisor.restarts += 1;
+            supervisor.last_restart = Some(time_now());
+
+            let delay = match unit {
+                ProcessUnit::Decoder => 0.0,
+                _ => supervisor.delay * 2_f64.powi(supervisor.failures as i32 - 1),
+            };
+
+            warn!(
+                "Restart {unit} in <yellow>{:.1}</> seconds, restart number: <yellow>{}</>",
+                delay.min(MAX_RESTART_DELAY),
+                supervisor.restarts
+            );
+
+            delay.min(MAX_RESTART_DELAY)
+        };
+
+        sleep(Duration::from_secs_f64(delay));
+
+        true
+    }
 }
 
 impl Default for ProcessControl {
@@ -75,6 +207,20 @@ impl Default for ProcessControl {
 }
 
 impl ProcessControl {
+    /// Check if the process from unit is started and not exited yet.
+    pub fn is_running(&self, unit: ProcessUnit) -> bool {
+        let term = match unit {
+            Decoder => &self.decoder_term,
+            Encoder => &self.encoder_term,
+            Ingest => &self.server_term,
+        };
+
+        term.lock()
+            .unwrap()
+            .as_mut()
+            .map_or(false, |p| matches!(p.try_wait(), Ok(None)))
+    }
+
     pub fn kill(&self, unit: ProcessUnit) -> Result<(), String> {
         match unit {
             Decoder => {
@@ -164,11 +310,14 @@ impl ProcessControl {
 // }
 
 /// Global player control, to get infos about current clip etc.
+///
+/// `hold` has the time, since when the playout is on hold and shows the slate.
 #[derive(Clone)]
 pub struct PlayerControl {
     pub current_media: Arc<Mutex<Option<Media>>>,
     pub current_list: Arc<Mutex<Vec<Media>>>,
     pub index: Arc<AtomicUsize>,
+    pub hold: Arc<Mutex<Option<DateTime<Local>>>>,
 }
 
 impl PlayerControl {
@@ -177,6 +326,7 @@ impl PlayerControl {
             current_media: Arc::new(Mutex::new(None)),
             current_list: Arc::new(Mutex::new(vec![Media::new(0, "", false)])),
             index: Arc::new(AtomicUsize::new(0)),
+            hold: Arc::new(Mutex::new(None)),
         }
     }
 }
@@ -188,6 +338,9 @@ impl Default for PlayerControl {
 }
 
 /// Global playout control, for move forward/backward clip, or resetting playlist/state.
+///
+/// `insert` holds a clip from the RPC insert command,
+/// it gets played before the next clip from source.
 #[derive(Clone, Debug)]
 pub struct PlayoutStatus {
     pub chain: Option<Arc<Mutex<Vec<String>>>>,
@@ -195,6 +348,7 @@ pub struct PlayoutStatus {
     pub date: Arc<Mutex<String>>,
     pub list_init: Arc<AtomicBool>,
     pub time_shift: Arc<Mutex<f64>>,
+    pub insert: Arc<Mutex<Option<Media>>>,
 }
 
 impl PlayoutStatus {
@@ -205,6 +359,7 @@ impl PlayoutStatus {
             date: Arc::new(Mutex::new(String::new())),
             list_init: Arc::new(AtomicBool::new(true)),
             time_shift: Arc::new(Mutex::new(0.0)),
+            insert: Arc::new(Mutex::new(None)),
         }
     }
 }
@@ -0,0 +1,248 @@
+/// Program Guide
+///
+/// Program items with the start times like the playout plays them, and their output
+/// as XMLTV document, or as events like in a DVB event information table (EIT).
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, Utc};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::utils::{
+    channel_instant, channel_time, channel_zone, json_serializer::JsonPlaylist, timed_playlist,
+    PlayoutConfig,
+};
+
+#[derive(Debug, Serialize)]
+pub struct ProgramItem {
+    #[serde(skip)]
+    pub begin: DateTime<FixedOffset>,
+    pub source: String,
+    pub start: String,
+    pub r#in: f64,
+    pub out: f64,
+    pub duration: f64,
+    pub category: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub title: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub subtitle: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub episode: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub season: Option<i32>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub external_id: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl ProgramItem {
+    /// Title for program guides, falls back to the source name.
+    fn name(&self) -> &str {
+        if self.title.is_empty() {
+            &self.source
+        } else {
+            &self.title
+        }
+    }
+
+    fn stop(&self) -> DateTime<FixedOffset> {
+        let stop = self.begin.with_timezone(&Utc)
+            + Duration::milliseconds(((self.out - self.r#in) * 1000.0) as i64);
+
+        stop.with_timezone(self.begin.offset())
+    }
+}
+
+/// Short event descriptor from an EIT event.
+#[derive(Debug, Serialize)]
+pub struct ShortEvent {
+    pub language: String,
+    pub event_name: String,
+    pub text: String,
+}
+
+/// Event in the style of an ETSI EN 300 468 event information table.
+#[derive(Debug, Serialize)]
+pub struct EitEvent {
+    pub event_id: u16,
+    pub start_time: String,
+    pub duration: String,
+    pub running_status: u8,
+    pub short_event: ShortEvent,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub extended_event: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub content: String,
+}
+
+/// Program from a playlist, with the start times like the playout plays it.
+///
+/// Times are in the channel time zone, so the wall time jumps on a DST change.
+/// Filler, which fills gaps before hard start times, is not listed.
+pub fn program_items(config: &PlayoutConfig, playlist: JsonPlaylist) -> Vec<ProgramItem> {
+    let tz = channel_zone(config);
+    let midnight = match NaiveDate::parse_from_str(&playlist.date, "%Y-%m-%d") {
+        Ok(date) => channel_instant(tz, date.and_hms_opt(0, 0, 0).unwrap()),
+        Err(_) => return vec![],
+    };
+    let regex = Regex::new(&config.text.regex).ok();
+
+    timed_playlist(config, playlist)
+        .program
+        .into_iter()
+        .filter(|item| !item.source.is_empty())
+        .map(|item| {
+            let begin = channel_time(
+                tz,
+                midnight
+                    + Duration::milliseconds(
+                        (item.begin.unwrap_or_default() * 1000.0).round() as i64
+                    ),
+            );
+            let source = regex
+                .as_ref()
+                .and_then(|r| r.captures(&item.source))
+                .and_then(|t| t.get(1).map(|m| m.as_str().to_string()))
+                .unwrap_or_else(|| item.source.clone());
+
+            ProgramItem {
+                begin,
+                source,
+                start: begin.format("%Y-%m-%d %H:%M:%S%.3f%:z").to_string(),
+                r#in: item.seek,
+                out: item.out,
+                duration: item.duration,
+                category: item.category,
+                title: item.title,
+                subtitle: item.subtitle,
+                description: item.description,
+                episode: item.episode,
+                season: item.season,
+                external_id: item.external_id,
+                metadata: item.metadata,
+            }
+        })
+        .collect()
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Create a XMLTV document from the program list.
+pub fn xmltv(channel_id: i32, channel_name: &str, program: &[ProgramItem]) -> String {
+    let time_format = "%Y%m%d%H%M%S %z";
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE tv SYSTEM \"xmltv.dtd\">\n",
+    );
+
+    xml.push_str("<tv generator-info-name=\"ffplayout\">\n");
+    xml.push_str(&format!(
+        "  <channel id=\"{channel_id}\">\n    <display-name>{}</display-name>\n  </channel>\n",
+        escape_xml(channel_name)
+    ));
+
+    for item in program {
+        xml.push_str(&format!(
+            "  <programme start=\"{}\" stop=\"{}\" channel=\"{channel_id}\">\n",
+            item.begin.format(time_format),
+            item.stop().format(time_format)
+        ));
+        xml.push_str(&format!("    <title>{}</title>\n", escape_xml(item.name())));
+
+        if !item.subtitle.is_empty() {
+            xml.push_str(&format!(
+                "    <sub-title>{}</sub-title>\n",
+                escape_xml(&item.subtitle)
+            ));
+        }
+
+        if !item.description.is_empty() {
+            xml.push_str(&format!(
+                "    <desc>{}</desc>\n",
+                escape_xml(&item.description)
+            ));
+        }
+
+        if !item.category.is_empty() {
+            xml.push_str(&format!(
+                "    <category>{}</category>\n",
+                escape_xml(&item.category)
+            ));
+        }
+
+        if item.season.is_some() || item.episode.is_some() {
+            // xmltv_ns counts from zero
+            let season = item
+                .season
+                .map(|s| (s - 1).max(0).to_string())
+                .unwrap_or_default();
+            let episode = item
+                .episode
+                .map(|e| (e - 1).max(0).to_string())
+                .unwrap_or_default();
+
+            xml.push_str(&format!(
+                "    <episode-num system=\"xmltv_ns\">{season}.{episode}.</episode-num>\n"
+            ));
+        }
+
+        xml.push_str("  </programme>\n");
+    }
+
+    xml.push_str("</tv>\n");
+
+    xml
+}
+
+/// Create EIT like events from the program list.
+///
+/// Start times are in UTC and durations in HH:MM:SS, like in the broadcast tables.
+pub fn eit(program: &[ProgramItem]) -> Vec<EitEvent> {
+    let now = Utc::now();
+
+    program
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let length = (item.out - item.r#in).round() as i64;
+            let running_status =
+                if item.begin.with_timezone(&Utc) <= now && now < item.stop().with_timezone(&Utc) {
+                    4
+                } else {
+                    1
+                };
+
+            EitEvent {
+                event_id: (i % u16::MAX as usize) as u16 + 1,
+                start_time: item
+                    .begin
+                    .with_timezone(&Utc)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string(),
+                duration: format!(
+                    "{:02}:{:02}:{:02}",
+                    length / 3600,
+                    (length % 3600) / 60,
+                    length % 60
+                ),
+                running_status,
+                short_event: ShortEvent {
+                    language: "und".to_string(),
+                    event_name: item.name().to_string(),
+                    text: item.subtitle.clone(),
+                },
+                extended_event: item.description.clone(),
+                content: item.category.clone(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,203 @@
+/// Export playlist to m3u/csv/edl file
+///
+/// The clips get their start time like in the playout, with expanded program blocks,
+/// hard start times and transitions, so the export shows what really plays.
+/// Gaps before a hard start are listed as filler.
+use std::{
+    fs::write,
+    io::{Error, ErrorKind},
+    path::Path,
+    str::FromStr,
+};
+
+use crate::utils::{
+    file_extension, json_reader, json_serializer::JsonPlaylist, sec_to_time, timed_playlist,
+    FrameRate, FrameTime, Media, PlayoutConfig,
+};
+
+/// Supported formats for the playlist export.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ExportFormat {
+    M3u,
+    Csv,
+    Edl,
+}
+
+impl ExportFormat {
+    /// Get the format from the file extension, m3u is the fallback.
+    pub fn from_path(path: &str) -> Self {
+        match file_extension(Path::new(path))
+            .map(|e| e.to_lowercase())
+            .as_deref()
+        {
+            Some("csv") => Self::Csv,
+            Some("edl") => Self::Edl,
+            _ => Self::M3u,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::M3u => "audio/x-mpegurl",
+            Self::Csv => "text/csv",
+            Self::Edl => "text/plain",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "m3u" | "m3u8" => Ok(Self::M3u),
+            "csv" => Ok(Self::Csv),
+            "edl" => Ok(Self::Edl),
+            _ => Err("Use 'm3u', 'csv' or 'edl'".to_string()),
+        }
+    }
+}
+
+/// Title from the clip, or the file name as fallback.
+fn clip_name(item: &Media) -> String {
+    if !item.title.is_empty() {
+        return item.title.clone();
+    }
+
+    if item.source.is_empty() {
+        return "filler".to_string();
+    }
+
+    Path::new(&item.source)
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_else(|| item.source.clone())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        return format!("\"{}\"", value.replace('"', "\"\""));
+    }
+
+    value.to_string()
+}
+
+fn to_m3u(program: &[Media]) -> String {
+    let mut m3u = String::from("#EXTM3U\n");
+
+    // filler has no source, the player not needs it
+    for item in program.iter().filter(|i| !i.source.is_empty()) {
+        m3u.push_str(&format!("#EXTINF:{},{}\n", item.duration, clip_name(item)));
+
+        if item.seek > 0.0 {
+            m3u.push_str(&format!("#EXTVLCOPT:start-time={}\n", item.seek));
+        }
+
+        if item.out < item.duration {
+            m3u.push_str(&format!("#EXTVLCOPT:stop-time={}\n", item.out));
+        }
+
+        m3u.push_str(&format!("{}\n", item.source));
+    }
+
+    m3u
+}
+
+fn to_csv(program: &[Media]) -> String {
+    let mut csv = String::from("start,source,in,out,duration,category,title\n");
+
+    for item in program {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            sec_to_time(item.begin.unwrap_or_default()),
+            csv_field(&item.source),
+            item.seek,
+            item.out,
+            item.duration,
+            csv_field(&item.category),
+            csv_field(&item.title)
+        ));
+    }
+
+    csv
+}
+
+fn to_edl(program: &[Media], title: &str, rate: FrameRate) -> String {
+    let mut edl = format!("TITLE: {title}\nFCM: NON-DROP FRAME\n");
+    let timecode = |sec: f64| FrameTime::from_sec(sec, rate).timecode();
+
+    for item in program {
+        let begin = FrameTime::from_sec(item.begin.unwrap_or_default(), rate);
+
+        edl.push_str(&format!(
+            "\n{:03}  AX       AA/V  C        {} {} {} {}\n",
+            item.index.unwrap_or_default() + 1,
+            timecode(item.seek),
+            timecode(item.out),
+            begin.timecode(),
+            (begin + item.frame_length(rate)).timecode()
+        ));
+        edl.push_str(&format!("* FROM CLIP NAME: {}\n", clip_name(item)));
+        edl.push_str(&format!("* SOURCE FILE: {}\n", item.source));
+    }
+
+    edl
+}
+
+/// Render playlist in the given format.
+pub fn export_playlist(
+    config: &PlayoutConfig,
+    playlist: &JsonPlaylist,
+    format: ExportFormat,
+) -> String {
+    let program = timed_playlist(config, playlist.clone()).program;
+
+    match format {
+        ExportFormat::M3u => to_m3u(&program),
+        ExportFormat::Csv => to_csv(&program),
+        ExportFormat::Edl => to_edl(
+            &program,
+            &format!("{} {}", playlist.channel, playlist.date),
+            FrameRate::from_fps(config.processing.fps),
+        ),
+    }
+}
+
+/// Read playlist from given date and write it to the export file.
+pub fn export_file(
+    config: &PlayoutConfig,
+    date: &str,
+    path: &str,
+    format: Option<ExportFormat>,
+) -> Result<String, Error> {
+    let d: Vec<&str> = date.split('-').collect();
+
+    if d.len() != 3 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Date <b><magenta>{date}</></b> is not valid!"),
+        ));
+    }
+
+    let playlist_file = Path::new(&config.playlist.path)
+        .join(d[0])
+        .join(d[1])
+        .join(format!("{date}.json"));
+
+    if !playlist_file.is_file() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!(
+                "Playlist <b><magenta>{}</></b> not exists!",
+                playlist_file.display()
+            ),
+        ));
+    }
+
+    let playlist = json_reader(&playlist_file)?;
+    let format = format.unwrap_or_else(|| ExportFormat::from_path(path));
+
+    write(path, export_playlist(config, &playlist, format))?;
+
+    Ok(format!("Export playlist from {date} to {path} success!"))
+}
@@ -0,0 +1,204 @@
+/// Filler Pool
+///
+/// When `filler_clip` in config is a folder, its clips (promos, idents, interstitials)
+/// are used to fill gaps. For a gap the combination of clips is chosen, which fills it best,
+/// clips which have run recently are only taken when the others are not enough.
+/// What is still missing then, is filled with a cut clip.
+///
+/// The folder is read again, when a clip is added or removed.
+use std::{collections::VecDeque, path::Path, sync::Mutex, time::SystemTime};
+
+use walkdir::WalkDir;
+
+use crate::utils::{include_file, Media, PlayoutConfig};
+
+/// Steps in which clip lengths are combined, in seconds.
+const FIT_STEP: f64 = 0.1;
+
+/// Rest, which is too short to fill.
+const MIN_REST: f64 = 0.04;
+
+static FILLER_POOL: Mutex<Option<FillerPool>> = Mutex::new(None);
+
+struct FillerPool {
+    path: String,
+    modified: Option<SystemTime>,
+    clips: Vec<Media>,
+    recent: VecDeque<String>,
+}
+
+/// Latest change in the folder and its subfolders.
+fn folder_modified(path: &str) -> Option<SystemTime> {
+    WalkDir::new(path)
+        .into_iter()
+        .flat_map(|e| e.ok())
+        .filter(|e| e.file_type().is_dir())
+        .filter_map(|e| e.metadata().ok().and_then(|m| m.modified().ok()))
+        .max()
+}
+
+impl FillerPool {
+    /// Read the filler folder, probed clips and recent list from `last` are kept.
+    fn new(config: &PlayoutConfig, last: Option<FillerPool>) -> Self {
+        let path = config.storage.filler_clip.clone();
+        let last = last.filter(|l| l.path == path);
+        let known = last.as_ref().map_or(&[][..], |l| &l.clips[..]);
+        let modified = folder_modified(&path);
+        let mut clips: Vec<Media> = WalkDir::new(&path)
+            .into_iter()
+            .flat_map(|e| e.ok())
+            .filter(|f| f.path().is_file() && include_file(config.clone(), f.path()))
+            .map(|f| {
+                let source = f.path().to_string_lossy();
+
+                match known.iter().find(|c| c.source == source) {
+                    Some(clip) => clip.clone(),
+                    None => Media::new(0, &source, true),
+                }
+            })
+            .filter(|m| m.duration > 0.0)
+            .collect();
+
+        clips.sort_by(|a, b| a.source.cmp(&b.source));
+
+        Self {
+            path,
+            modified,
+            clips,
+            recent: last.map(|l| l.recent).unwrap_or_default(),
+        }
+    }
+
+    fn remember(&mut self, clips: &[Media]) {
+        let max = (self.clips.len() / 2).max(1);
+
+        for clip in clips {
+            self.recent.retain(|s| s != &clip.source);
+            self.recent.push_back(clip.source.clone());
+        }
+
+        while self.recent.len() > max {
+            self.recent.pop_front();
+        }
+    }
+}
+
+/// Check if filler in config is a folder.
+pub fn is_filler_pool(config: &PlayoutConfig) -> bool {
+    !config.storage.filler_clip.is_empty() && Path::new(&config.storage.filler_clip).is_dir()
+}
+
+/// Get clips from the filler folder, which fill `length` seconds.
+///
+/// Returns an empty list, when there is no filler folder, or it contains no usable clips.
+pub fn fill_gap(config: &PlayoutConfig, length: f64) -> Vec<Media> {
+    if !is_filler_pool(config) || length < MIN_REST {
+        return vec![];
+    }
+
+    let mut pool = FILLER_POOL.lock().unwrap();
+
+    if pool.as_ref().map_or(true, |p| {
+        p.path != config.storage.filler_clip
+            || p.modified != folder_modified(&config.storage.filler_clip)
+    }) {
+        *pool = Some(FillerPool::new(config, pool.take()));
+    }
+
+    let pool = pool.as_mut().unwrap();
+    let clips = best_fit(&pool.clips, &pool.recent, length);
+
+    pool.remember(&clips);
+
+    clips
+}
+
+/// Choose clips, which fill `length` as good as possible.
+///
+/// Long gaps get filled in rotation, until the rest can be fitted. The rest is fitted
+/// with the combination, which leaves the smallest gap, and this gap gets filled by a cut clip.
+/// Clips from `recent` come last, oldest first.
+pub fn best_fit(clips: &[Media], recent: &VecDeque<String>, length: f64) -> Vec<Media> {
+    let mut candidates: Vec<&Media> = clips
+        .iter()
+        .filter(|c| c.duration > 0.0 && !recent.contains(&c.source))
+        .collect();
+
+    for source in recent {
+        candidates.extend(clips.iter().filter(|c| &c.source == source));
+    }
+
+    let mut result = vec![];
+
+    if candidates.is_empty() {
+        return result;
+    }
+
+    let max_duration = candidates.iter().map(|c| c.duration).fold(0.0, f64::max);
+    let mut rest = length;
+    let mut rotation = candidates.iter().cycle();
+
+    while rest > max_duration * 2.0 {
+        let clip = rotation.next().unwrap();
+
+        rest -= clip.duration;
+        result.push((*clip).clone());
+    }
+
+    // subset sum, in steps of FIT_STEP: reachable[sum] = (previous sum, candidate)
+    // lengths are rounded up, so the fitted clips are never longer than the rest
+    let steps = |d: f64| (d / FIT_STEP).ceil() as usize;
+    let max_sum = (rest / FIT_STEP).floor() as usize;
+    let mut reachable: Vec<Option<(usize, usize)>> = vec![None; max_sum + 1];
+    let mut best = 0;
+
+    for (i, clip) in candidates.iter().enumerate() {
+        let len = steps(clip.duration).max(1);
+
+        for sum in (len..=max_sum).rev() {
+            if reachable[sum].is_none() && (sum == len || reachable[sum - len].is_some()) {
+                reachable[sum] = Some((sum - len, i));
+                best = best.max(sum);
+            }
+        }
+    }
+
+    let mut fitted = vec![];
+    let mut sum = best;
+
+    while sum > 0 {
+        let (prev, i) = reachable[sum].unwrap();
+        fitted.push(i);
+        sum = prev;
+    }
+
+    fitted.reverse();
+
+    for i in &fitted {
+        rest -= candidates[*i].duration;
+        result.push(candidates[*i].clone());
+    }
+
+    // cut clip for what is left, not used clips first
+    let mut order: Vec<&Media> = candidates
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !fitted.contains(i))
+        .chain(candidates.iter().enumerate())
+        .map(|(_, c)| *c)
+        .collect();
+
+    order.sort_by_key(|c| c.duration < rest);
+
+    let mut order = order.into_iter().cycle();
+
+    while rest > MIN_REST {
+        let mut clip = order.next().unwrap().clone();
+
+        clip.out = clip.duration.min(rest);
+        rest -= clip.out;
+        result.push(clip);
+    }
+
+    result
+}
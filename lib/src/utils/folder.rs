@@ -1,5 +1,7 @@
 use std::{
-    path::Path,
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
     process::exit,
     sync::{
         atomic::{AtomicUsize, Ordering},
@@ -7,15 +9,93 @@ use std::{
     },
 };
 
-use rand::{seq::SliceRandom, thread_rng};
 use simplelog::*;
 use walkdir::WalkDir;
 
-use crate::utils::{get_sec, include_file, Media, PlayoutConfig};
+use crate::utils::{
+    get_sec, include_file, shuffle_clips, state_file, Media, PlayHistory, PlayoutConfig, Rotation,
+};
+
+/// Order and position from folder mode, which are saved beside the status file.
+///
+/// The order is written only when the list changes, the position for every clip.
+#[derive(Debug, Default)]
+pub struct FolderState {
+    pub index: usize,
+    pub order: Vec<String>,
+}
+
+impl FolderState {
+    fn load(order_file: &Path, index_file: &Path) -> Option<Self> {
+        let order = fs::read_to_string(order_file)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())?;
+        let index = fs::read_to_string(index_file)
+            .ok()
+            .and_then(|i| i.trim().parse().ok())
+            .unwrap_or_default();
+
+        Some(Self { index, order })
+    }
+
+    /// Saved order with the files, which still exist, and the index from the clip to resume with.
+    ///
+    /// New files are added at the end, in sorted mode the list gets sorted again.
+    /// Returns None, when no file from the saved order exists anymore.
+    pub fn restore(&self, config: &PlayoutConfig, clips: &[Media]) -> Option<(Vec<Media>, usize)> {
+        let known: HashSet<&str> = clips.iter().map(|c| c.source.as_str()).collect();
+        let saved: HashSet<&str> = self.order.iter().map(|s| s.as_str()).collect();
+        let mut list: Vec<Media> = self
+            .order
+            .iter()
+            .filter(|s| known.contains(s.as_str()))
+            .map(|s| Media::new(0, s, false))
+            .collect();
+
+        if list.is_empty() {
+            return None;
+        }
+
+        let mut index = self
+            .order
+            .iter()
+            .take(self.index)
+            .filter(|s| known.contains(s.as_str()))
+            .count();
+
+        // with rotation rules new files come with the next round
+        if config.storage.rotation.is_empty() {
+            list.extend(
+                clips
+                    .iter()
+                    .filter(|c| !saved.contains(c.source.as_str()))
+                    .cloned(),
+            );
+
+            if !config.storage.shuffle {
+                let resume = self
+                    .order
+                    .iter()
+                    .skip(self.index)
+                    .find(|s| known.contains(s.as_str()));
+
+                list.sort_by(|d1, d2| d1.source.cmp(&d2.source));
+                index = resume
+                    .and_then(|s| list.iter().position(|c| &c.source == s))
+                    .unwrap_or(list.len());
+            }
+        }
+
+        Some((list, index))
+    }
+}
 
 /// Folder Sources
 ///
 /// Like playlist source, we create here a folder list for iterate over it.
+/// With rotation rules in config, the list is build in rounds from the rule folders.
+/// Shuffled lists keep the no-repeat window, over the recent history.
+/// Order and position are saved, so after a restart the playout resumes with the last clip.
 #[derive(Debug, Clone)]
 pub struct FolderSource {
     config: PlayoutConfig,
@@ -23,6 +103,12 @@ pub struct FolderSource {
     pub nodes: Arc<Mutex<Vec<Media>>>,
     current_node: Media,
     index: Arc<AtomicUsize>,
+    rotation: Option<Rotation>,
+    history: PlayHistory,
+    state_file: Option<PathBuf>,
+    index_file: Option<PathBuf>,
+    saved_order: Vec<String>,
+    time: Option<f64>,
 }
 
 impl FolderSource {
@@ -63,10 +149,35 @@ impl FolderSource {
             exit(1);
         }
 
-        if config.storage.shuffle {
+        let mut rotation = Rotation::new(config, &media_list);
+        let history = PlayHistory::new(config);
+        let state_file = state_file(config, "folder");
+        let index_file = state_file(config, "folder_index");
+        let restored = state_file
+            .as_ref()
+            .zip(index_file.as_ref())
+            .and_then(|(order, index)| FolderState::load(order, index))
+            .and_then(|state| state.restore(config, &media_list));
+
+        if let Some((list, position)) = restored {
+            info!("Resume folder list at position <yellow>{position}</>");
+            media_list = list;
+            global_index.store(position, Ordering::SeqCst);
+        } else if let Some(rotation) = rotation.as_mut() {
+            info!("Rotate files by rules");
+            media_list = rotation.round();
+
+            if media_list.is_empty() {
+                error!(
+                    "no files for rotation found under: <b><magenta>{}</></b>",
+                    config.storage.path
+                );
+
+                exit(1);
+            }
+        } else if config.storage.shuffle {
             info!("Shuffle files");
-            let mut rng = thread_rng();
-            media_list.shuffle(&mut rng);
+            shuffle_clips(config, &mut media_list, &history);
         } else {
             media_list.sort_by(|d1, d2| d1.source.cmp(&d2.source));
         }
@@ -85,20 +196,78 @@ impl FolderSource {
             nodes: current_list,
             current_node: Media::new(0, "", false),
             index: global_index,
+            rotation,
+            history,
+            state_file,
+            index_file,
+            saved_order: vec![],
+            time: None,
+        }
+    }
+
+    /// Next clip, which begins at `time`, in seconds since midnight.
+    ///
+    /// Used from the playlist generator, where the begin is not the current time.
+    pub fn next_at(&mut self, time: f64) -> Option<Media> {
+        self.time = Some(time);
+        self.next()
+    }
+
+    fn rotate(&mut self) {
+        if let Some(rotation) = self.rotation.as_mut() {
+            let mut nodes = self.nodes.lock().unwrap();
+
+            // files from folder watcher
+            rotation.update(&nodes);
+            *nodes = rotation.round();
+
+            for (index, item) in nodes.iter_mut().enumerate() {
+                item.index = Some(index);
+            }
         }
     }
 
     fn shuffle(&mut self) {
-        let mut rng = thread_rng();
         let mut nodes = self.nodes.lock().unwrap();
 
-        nodes.shuffle(&mut rng);
+        shuffle_clips(&self.config, &mut nodes, &self.history);
 
         for (index, item) in nodes.iter_mut().enumerate() {
             item.index = Some(index);
         }
     }
 
+    /// Add current clip to the history, which is needed for shuffle only.
+    fn remember(&mut self) {
+        if self.config.storage.shuffle && self.rotation.is_none() {
+            self.history.add(&self.current_node);
+        }
+    }
+
+    /// Save order and position, the current clip is played again after a restart.
+    ///
+    /// The order is only written, when the list has changed since the last save.
+    fn save_state(&mut self, index: usize) {
+        if let (Some(order_file), Some(index_file)) = (&self.state_file, &self.index_file) {
+            let nodes = self.nodes.lock().unwrap();
+
+            if !nodes.iter().map(|n| &n.source).eq(self.saved_order.iter()) {
+                self.saved_order = nodes.iter().map(|n| n.source.clone()).collect();
+
+                if let Err(e) = fs::write(
+                    order_file,
+                    serde_json::to_string(&self.saved_order).unwrap(),
+                ) {
+                    error!("Unable to write folder order <b><magenta>{order_file:?}</></b>: {e}");
+                }
+            }
+
+            if let Err(e) = fs::write(index_file, index.to_string()) {
+                error!("Unable to write folder position <b><magenta>{index_file:?}</></b>: {e}");
+            }
+        }
+    }
+
     fn sort(&mut self) {
         let mut nodes = self.nodes.lock().unwrap();
 
@@ -115,19 +284,39 @@ impl Iterator for FolderSource {
     type Item = Media;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let time = self.time.take().unwrap_or_else(get_sec);
+
+        // clip for the top of the hour, the list position stays
+        if let Some(mut clip) = self.rotation.as_mut().and_then(|r| r.hourly(time)) {
+            clip.index = Some(self.index.load(Ordering::SeqCst).saturating_sub(1));
+            clip.add_probe();
+            clip.add_filter(&self.config, &self.filter_chain);
+            clip.begin = Some(time);
+
+            return Some(clip);
+        }
+
         if self.index.load(Ordering::SeqCst) < self.nodes.lock().unwrap().len() {
             let i = self.index.load(Ordering::SeqCst);
             self.current_node = self.nodes.lock().unwrap()[i].clone();
             self.current_node.add_probe();
             self.current_node
                 .add_filter(&self.config, &self.filter_chain);
-            self.current_node.begin = Some(get_sec());
+            self.current_node.begin = Some(time);
+            self.remember();
+            self.save_state(i);
 
             self.index.fetch_add(1, Ordering::SeqCst);
 
             Some(self.current_node.clone())
         } else {
-            if self.config.storage.shuffle {
+            if self.rotation.is_some() {
+                if self.config.general.generate.is_none() {
+                    info!("Rotate files by rules");
+                }
+
+                self.rotate();
+            } else if self.config.storage.shuffle {
                 if self.config.general.generate.is_none() {
                     info!("Shuffle files");
                 }
@@ -145,7 +334,9 @@ impl Iterator for FolderSource {
             self.current_node.add_probe();
             self.current_node
                 .add_filter(&self.config, &self.filter_chain);
-            self.current_node.begin = Some(get_sec());
+            self.current_node.begin = Some(time);
+            self.remember();
+            self.save_state(0);
 
             self.index.store(1, Ordering::SeqCst);
 
@@ -0,0 +1,233 @@
+/// Frame Accurate Time
+///
+/// Positions and lengths are counted in frames from the output frame rate.
+/// Adding up clips over a full day gives no rounding drift,
+/// and cuts land exactly on frame boundaries.
+use std::{
+    cmp::Ordering,
+    fmt,
+    ops::{Add, AddAssign, Sub, SubAssign},
+    str::FromStr,
+};
+
+fn gcd(a: i64, b: i64) -> i64 {
+    match b {
+        0 => a.abs().max(1),
+        _ => gcd(b, a % b),
+    }
+}
+
+/// Frame rate as fraction, like 25/1 or 30000/1001.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct FrameRate {
+    pub num: i64,
+    pub den: i64,
+}
+
+impl FrameRate {
+    pub fn new(num: i64, den: i64) -> Self {
+        let (num, den) = (num.max(1), den.max(1));
+        let div = gcd(num, den);
+
+        Self {
+            num: num / div,
+            den: den / div,
+        }
+    }
+
+    /// Get the exact fraction from a frame rate number, NTSC rates like 29.97 becomes x/1001.
+    pub fn from_fps(fps: f64) -> Self {
+        if !fps.is_finite() || fps <= 0.0 {
+            return Self::new(25, 1);
+        }
+
+        let rounded = fps.round();
+
+        if (fps - rounded).abs() < 0.001 {
+            return Self::new(rounded as i64, 1);
+        }
+
+        let ntsc = (fps * 1.001).round();
+
+        if (fps - ntsc / 1.001).abs() < 0.001 {
+            return Self::new(ntsc as i64 * 1000, 1001);
+        }
+
+        Self::new((fps * 1000.0).round() as i64, 1000)
+    }
+
+    pub fn fps(&self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+}
+
+impl Default for FrameRate {
+    fn default() -> Self {
+        Self::new(25, 1)
+    }
+}
+
+impl FromStr for FrameRate {
+    type Err = String;
+
+    /// Parse fraction (like ffprobe r_frame_rate) or number.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let error = || format!("Frame rate <b><magenta>{input}</></b> is not valid!");
+
+        match input.split_once('/') {
+            Some((num, den)) => {
+                let num = num.trim().parse::<i64>().map_err(|_| error())?;
+                let den = den.trim().parse::<i64>().map_err(|_| error())?;
+
+                if num <= 0 || den <= 0 {
+                    return Err(error());
+                }
+
+                Ok(Self::new(num, den))
+            }
+            None => match input.trim().parse::<f64>() {
+                Ok(fps) if fps > 0.0 => Ok(Self::from_fps(fps)),
+                _ => Err(error()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for FrameRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.num, self.den)
+    }
+}
+
+/// Time position or length as number of frames.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameTime {
+    frames: i64,
+    rate: FrameRate,
+}
+
+impl FrameTime {
+    pub fn new(frames: i64, rate: FrameRate) -> Self {
+        Self { frames, rate }
+    }
+
+    pub fn zero(rate: FrameRate) -> Self {
+        Self::new(0, rate)
+    }
+
+    /// Round seconds to the nearest frame.
+    pub fn from_sec(sec: f64, rate: FrameRate) -> Self {
+        Self::new(
+            (sec * rate.num as f64 / rate.den as f64).round() as i64,
+            rate,
+        )
+    }
+
+    pub fn frames(&self) -> i64 {
+        self.frames
+    }
+
+    pub fn rate(&self) -> FrameRate {
+        self.rate
+    }
+
+    pub fn sec(&self) -> f64 {
+        (self.frames * self.rate.den) as f64 / self.rate.num as f64
+    }
+
+    /// Convert to other frame rate, rounded to the nearest frame.
+    pub fn with_rate(&self, rate: FrameRate) -> Self {
+        if rate == self.rate {
+            return *self;
+        }
+
+        let num = self.frames as i128 * self.rate.den as i128 * rate.num as i128;
+        let den = self.rate.num as i128 * rate.den as i128;
+        let half = if num < 0 { -den } else { den };
+
+        Self::new(((num * 2 + half) / (den * 2)) as i64, rate)
+    }
+
+    /// SMPTE timecode (HH:MM:SS:FF), with the nominal frame rate and no drop frames.
+    pub fn timecode(&self) -> String {
+        let fps = ((self.rate.num + self.rate.den - 1) / self.rate.den).max(1);
+        let frames = self.frames.max(0);
+        let s = frames / fps;
+
+        format!(
+            "{:02}:{:02}:{:02}:{:02}",
+            s / 3600,
+            (s % 3600) / 60,
+            s % 60,
+            frames % fps
+        )
+    }
+
+    fn cross(&self, other: &Self) -> (i128, i128) {
+        (
+            self.frames as i128 * self.rate.den as i128 * other.rate.num as i128,
+            other.frames as i128 * other.rate.den as i128 * self.rate.num as i128,
+        )
+    }
+}
+
+/// Seconds with up to six decimals, like ffmpeg filters and options expects them.
+impl fmt::Display for FrameTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sec = format!("{:.6}", self.sec());
+
+        write!(f, "{}", sec.trim_end_matches('0').trim_end_matches('.'))
+    }
+}
+
+impl PartialEq for FrameTime {
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b) = self.cross(other);
+
+        a == b
+    }
+}
+
+impl Eq for FrameTime {}
+
+impl PartialOrd for FrameTime {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FrameTime {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a, b) = self.cross(other);
+
+        a.cmp(&b)
+    }
+}
+
+impl Add for FrameTime {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.frames + other.with_rate(self.rate).frames, self.rate)
+    }
+}
+
+impl AddAssign for FrameTime {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for FrameTime {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.frames - other.with_rate(self.rate).frames, self.rate)
+    }
+}
+
+impl SubAssign for FrameTime {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
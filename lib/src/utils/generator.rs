@@ -5,22 +5,325 @@
 /// The generator takes the files from storage, which are set in config.
 /// It also respect the shuffle/sort mode.
 ///
-/// Beside that it is really very basic, without any logic.
+/// When a weekly template is set in config, every day gets build from the time slots
+/// of its weekday. Slots can point to folders, categories or fixed files.
+/// Without template, the rotation rules from storage config are used.
 use std::{
-    fs::{create_dir_all, write},
-    io::Error,
+    collections::HashMap,
+    fs::{create_dir_all, write, File},
+    io::{Error, ErrorKind},
     path::Path,
     process::exit,
     sync::{atomic::AtomicUsize, Arc, Mutex},
 };
 
+use chrono::{Datelike, NaiveDate, Weekday};
+use rand::{seq::SliceRandom, thread_rng};
+use serde::{Deserialize, Serialize};
 use simplelog::*;
+use walkdir::WalkDir;
 
 use super::folder::FolderSource;
 use crate::utils::{
-    get_date_range, json_serializer::JsonPlaylist, time_to_sec, Media, PlayoutConfig,
+    broadcast_day, channel_zone, fill_gap, get_date_range, include_file, is_filler_pool,
+    json_serializer::JsonPlaylist, real_sec, time_to_sec, Media, PlayoutConfig,
 };
 
+/// Time slot from a weekly template.
+///
+/// `source` can be a folder or a single file. When it is empty, the files are taken
+/// from the subfolder in storage path, which is named like the `category`.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct TemplateSlot {
+    pub start: String,
+    #[serde(default)]
+    pub duration: String,
+    #[serde(default)]
+    pub source: String,
+    #[serde(default)]
+    pub category: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub shuffle: Option<bool>,
+}
+
+/// Weekly template, days without slots use the `default` slots.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct WeeklyTemplate {
+    #[serde(default)]
+    pub monday: Vec<TemplateSlot>,
+    #[serde(default)]
+    pub tuesday: Vec<TemplateSlot>,
+    #[serde(default)]
+    pub wednesday: Vec<TemplateSlot>,
+    #[serde(default)]
+    pub thursday: Vec<TemplateSlot>,
+    #[serde(default)]
+    pub friday: Vec<TemplateSlot>,
+    #[serde(default)]
+    pub saturday: Vec<TemplateSlot>,
+    #[serde(default)]
+    pub sunday: Vec<TemplateSlot>,
+    #[serde(default)]
+    pub default: Vec<TemplateSlot>,
+}
+
+impl WeeklyTemplate {
+    /// Read template from JSON or YAML file.
+    pub fn new(path: &str) -> Result<Self, Error> {
+        let file = File::open(path)?;
+
+        if path.to_lowercase().ends_with(".json") {
+            Ok(serde_json::from_reader(file)?)
+        } else {
+            serde_yaml::from_reader(file).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+        }
+    }
+
+    pub fn slots(&self, weekday: Option<Weekday>) -> &[TemplateSlot] {
+        let slots = match weekday {
+            Some(Weekday::Mon) => &self.monday,
+            Some(Weekday::Tue) => &self.tuesday,
+            Some(Weekday::Wed) => &self.wednesday,
+            Some(Weekday::Thu) => &self.thursday,
+            Some(Weekday::Fri) => &self.friday,
+            Some(Weekday::Sat) => &self.saturday,
+            Some(Weekday::Sun) => &self.sunday,
+            None => &self.default,
+        };
+
+        if slots.is_empty() {
+            &self.default
+        } else {
+            slots
+        }
+    }
+}
+
+/// Folder lists and their position, so folders continue over multiple days.
+#[derive(Debug, Default)]
+struct TemplateState {
+    folders: HashMap<String, Vec<Media>>,
+    cursors: HashMap<String, usize>,
+}
+
+/// Parse time in HH:MM:SS, invalid values gives None.
+fn clock_to_sec(time: &str) -> Option<f64> {
+    let t: Vec<f64> = time
+        .split(':')
+        .map(|t| t.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .ok()?;
+
+    if t.len() != 3 {
+        return None;
+    }
+
+    Some(t[0] * 3600.0 + t[1] * 60.0 + t[2])
+}
+
+/// Fill given length with clips from the filler folder, or with the filler clip,
+/// or with empty sources when filler is not usable.
+fn filler_clips(config: &PlayoutConfig, mut length: f64) -> Vec<Media> {
+    let mut clips = vec![];
+
+    if length <= 0.01 {
+        return clips;
+    }
+
+    clips = fill_gap(config, length);
+
+    if !clips.is_empty() {
+        return clips;
+    }
+
+    let filler = filler_media(config);
+
+    while length > 0.01 {
+        let mut item = if filler.duration > 0.0 {
+            filler.clone()
+        } else {
+            let mut item = Media::new(0, "", false);
+            item.duration = length;
+            item
+        };
+
+        item.out = item.duration.min(length);
+        length -= item.out;
+
+        clips.push(item);
+    }
+
+    clips
+}
+
+/// Single filler clip, a filler folder has no duration.
+fn filler_media(config: &PlayoutConfig) -> Media {
+    match is_filler_pool(config) {
+        true => Media::new(0, "", false),
+        false => Media::new(0, &config.storage.filler_clip, true),
+    }
+}
+
+fn folder_media(config: &PlayoutConfig, path: &Path, shuffle: bool) -> Vec<Media> {
+    let mut media_list: Vec<Media> = WalkDir::new(path)
+        .into_iter()
+        .flat_map(|e| e.ok())
+        .filter(|f| f.path().is_file() && include_file(config.clone(), f.path()))
+        .map(|f| Media::new(0, &f.path().to_string_lossy(), false))
+        .collect();
+
+    if shuffle {
+        media_list.shuffle(&mut thread_rng());
+    } else {
+        media_list.sort_by(|d1, d2| d1.source.cmp(&d2.source));
+    }
+
+    media_list
+}
+
+/// Get the clips for one slot, the remaining time is filled with filler.
+fn slot_clips(
+    config: &PlayoutConfig,
+    slot: &TemplateSlot,
+    length: f64,
+    state: &mut TemplateState,
+) -> Vec<Media> {
+    let mut clips = vec![];
+    let mut filled = 0.0;
+    let mut source = slot.source.clone();
+
+    if source.is_empty() && !slot.category.is_empty() {
+        source = Path::new(&config.storage.path)
+            .join(&slot.category)
+            .to_string_lossy()
+            .to_string();
+    }
+
+    let path = Path::new(&source);
+
+    if path.is_file() {
+        let mut item = Media::new(0, &source, true);
+
+        if item.duration > 0.0 {
+            item.out = item.duration.min(length);
+            filled = item.out;
+            clips.push(item);
+        }
+    } else if path.is_dir() {
+        let list = state.folders.entry(source.clone()).or_insert_with(|| {
+            folder_media(config, path, slot.shuffle.unwrap_or(config.storage.shuffle))
+        });
+        let cursor = state.cursors.entry(source.clone()).or_insert(0);
+        let mut misses = 0;
+
+        while misses < list.len() {
+            let item = &mut list[*cursor % list.len()];
+            item.add_probe();
+
+            if item.duration > 0.0 && filled + item.duration <= length {
+                filled += item.duration;
+                clips.push(item.clone());
+                misses = 0;
+            } else {
+                misses += 1;
+            }
+
+            *cursor = (*cursor + 1) % list.len();
+        }
+    } else {
+        warn!("Template source not exists: <b><magenta>{source}</></b>");
+    }
+
+    for clip in clips.iter_mut() {
+        if !slot.category.is_empty() {
+            clip.category = slot.category.clone();
+        }
+
+        if !slot.title.is_empty() {
+            clip.title = slot.title.clone();
+        }
+    }
+
+    clips.append(&mut filler_clips(config, length - filled));
+
+    clips
+}
+
+/// Build the program for one day from template slots.
+///
+/// The first clip of every slot gets a hard start, so the engine stays in sync with the template.
+/// Slot offsets are in real seconds from the day start, like the playout counts them.
+fn template_program(
+    config: &PlayoutConfig,
+    day: Option<NaiveDate>,
+    slots: &[TemplateSlot],
+    total_length: f64,
+    state: &mut TemplateState,
+) -> Vec<Media> {
+    let day_start = config.playlist.start_sec.unwrap_or(0.0);
+    let tz = channel_zone(config);
+    let offset = |sec: f64| {
+        let wall = day_start + (sec - day_start).rem_euclid(86400.0);
+
+        match day {
+            Some(d) => real_sec(tz, d, wall) - broadcast_day(tz, d, day_start).0,
+            None => wall - day_start,
+        }
+    };
+    let mut program = vec![];
+    let mut length = 0.0;
+    let mut timed_slots: Vec<(f64, &TemplateSlot)> = slots
+        .iter()
+        .filter_map(|slot| match clock_to_sec(&slot.start) {
+            Some(sec) => Some((offset(sec), slot)),
+            None => {
+                warn!(
+                    "Template slot has no valid start time: <b><magenta>{}</></b>",
+                    slot.start
+                );
+                None
+            }
+        })
+        .collect();
+
+    timed_slots.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    for (i, (offset, slot)) in timed_slots.iter().enumerate() {
+        if *offset >= total_length {
+            break;
+        }
+
+        let end = timed_slots
+            .get(i + 1)
+            .map(|s| s.0)
+            .unwrap_or(total_length)
+            .min(total_length);
+        let slot_length = match clock_to_sec(&slot.duration) {
+            Some(duration) if duration > 0.0 => duration.min(end - offset),
+            _ => end - offset,
+        };
+
+        if length < *offset {
+            program.append(&mut filler_clips(config, offset - length));
+        }
+
+        let mut clips = slot_clips(config, slot, slot_length, state);
+
+        if let Some(first) = clips.first_mut() {
+            first.start = Some(slot.start.trim().to_string());
+        }
+
+        length = offset + slot_length;
+        program.append(&mut clips);
+    }
+
+    program.append(&mut filler_clips(config, total_length - length));
+
+    program
+}
+
 /// Generate playlists
 pub fn generate_playlist(
     config: &PlayoutConfig,
@@ -41,6 +344,9 @@ pub fn generate_playlist(
     let playlist_root = Path::new(&config.playlist.path);
     let mut playlists = vec![];
     let mut date_range = vec![];
+    let mut media_list = None;
+    let mut template = None;
+    let mut template_state = TemplateState::default();
 
     let channel = match channel_name {
         Some(name) => name,
@@ -56,6 +362,10 @@ pub fn generate_playlist(
         exit(1);
     }
 
+    if !config.playlist.template.is_empty() {
+        template = Some(WeeklyTemplate::new(&config.playlist.template)?);
+    }
+
     if let Some(range) = config.general.generate.clone() {
         date_range = range;
     }
@@ -64,9 +374,6 @@ pub fn generate_playlist(
         date_range = get_date_range(&date_range)
     }
 
-    let media_list = FolderSource::new(config, None, current_list, index);
-    let list_length = media_list.nodes.lock().unwrap().len();
-
     for date in date_range {
         let d: Vec<&str> = date.split('-').collect();
         let year = d[0];
@@ -90,10 +397,24 @@ pub fn generate_playlist(
             playlist_file.display()
         );
 
-        let mut filler = Media::new(0, &config.storage.filler_clip, true);
-        let filler_length = filler.duration;
-        let mut length = 0.0;
-        let mut round = 0;
+        let day = NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok();
+        let slots = template
+            .as_ref()
+            .map(|t| t.slots(day.map(|d| d.weekday())))
+            .unwrap_or(&[]);
+
+        // a full day is shorter or longer, when the DST changes
+        let total_length = match day {
+            Some(d) if total_length == 86400.0 => {
+                broadcast_day(
+                    channel_zone(config),
+                    d,
+                    config.playlist.start_sec.unwrap_or(0.0),
+                )
+                .1
+            }
+            _ => total_length,
+        };
 
         let mut playlist = JsonPlaylist {
             channel: channel.clone(),
@@ -104,22 +425,43 @@ pub fn generate_playlist(
             program: vec![],
         };
 
-        for item in media_list.clone() {
-            let duration = item.duration;
+        if !slots.is_empty() {
+            playlist.program =
+                template_program(config, day, slots, total_length, &mut template_state);
+        } else {
+            let media_list = media_list.get_or_insert_with(|| {
+                FolderSource::new(config, None, current_list.clone(), index.clone())
+            });
+            let list_length = media_list.nodes.lock().unwrap().len();
+            let mut filler = filler_media(config);
+            let filler_length = filler.duration;
+            let day_start = config.playlist.start_sec.unwrap_or(0.0);
+            let mut length = 0.0;
+            let mut round = 0;
 
-            if total_length > length + duration {
-                playlist.program.push(item);
+            while let Some(item) = media_list.next_at(day_start + length) {
+                let duration = item.duration;
 
-                length += duration;
-            } else if filler_length > 0.0 && filler_length > total_length - length {
-                filler.out = total_length - length;
-                playlist.program.push(filler);
+                if total_length > length + duration {
+                    playlist.program.push(item);
 
-                break;
-            } else if round == list_length - 1 {
-                break;
-            } else {
-                round += 1;
+                    length += duration;
+                } else if is_filler_pool(config) {
+                    playlist
+                        .program
+                        .append(&mut fill_gap(config, total_length - length));
+
+                    break;
+                } else if filler_length > 0.0 && filler_length > total_length - length {
+                    filler.out = total_length - length;
+                    playlist.program.push(filler);
+
+                    break;
+                } else if round == list_length - 1 {
+                    break;
+                } else {
+                    round += 1;
+                }
             }
         }
 
@@ -1,21 +1,411 @@
-/// Import text/m3u file and create a playlist out of it
+/// Import text/m3u/csv/xspf/edl file and create a playlist out of it
 use std::{
     //error::Error,
-    fs::{create_dir_all, File},
+    fs::{create_dir_all, read_to_string, File},
     io::{BufRead, BufReader, Error, ErrorKind},
+    mem,
     path::Path,
+    str::FromStr,
 };
 
-use crate::utils::{json_reader, json_serializer::JsonPlaylist, json_writer, Media, PlayoutConfig};
+use regex::Regex;
+
+use crate::utils::{
+    file_extension, json_reader, json_serializer::JsonPlaylist, json_writer, Media, PlayoutConfig,
+};
+
+/// Supported formats for the playlist import.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ImportFormat {
+    Text,
+    M3u,
+    Csv,
+    Xspf,
+    Edl,
+}
+
+impl ImportFormat {
+    /// Get the format from the file extension, plain text is the fallback.
+    pub fn from_path(path: &str) -> Self {
+        match file_extension(Path::new(path))
+            .map(|e| e.to_lowercase())
+            .as_deref()
+        {
+            Some("m3u") | Some("m3u8") => Self::M3u,
+            Some("csv") => Self::Csv,
+            Some("xspf") => Self::Xspf,
+            Some("edl") => Self::Edl,
+            _ => Self::Text,
+        }
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "text" | "txt" => Ok(Self::Text),
+            "m3u" | "m3u8" => Ok(Self::M3u),
+            "csv" => Ok(Self::Csv),
+            "xspf" => Ok(Self::Xspf),
+            "edl" => Ok(Self::Edl),
+            _ => Err("Use 'text', 'm3u', 'csv', 'xspf' or 'edl'".to_string()),
+        }
+    }
+}
+
+/// Parse time values in seconds, or in the form of HH:MM:SS.mmm / MM:SS.
+fn parse_time(value: &str) -> f64 {
+    value
+        .trim()
+        .split(':')
+        .map(|t| t.trim().parse::<f64>().unwrap_or(0.0))
+        .fold(0.0, |sum, t| sum * 60.0 + t)
+}
+
+/// Convert a SMPTE timecode (HH:MM:SS:FF) to seconds.
+fn timecode_to_sec(timecode: &str, fps: f64) -> f64 {
+    let t: Vec<f64> = timecode
+        .split(|c: char| c == ':' || c == ';' || c == '.')
+        .map(|t| t.parse().unwrap_or(0.0))
+        .collect();
+
+    if t.len() != 4 {
+        return 0.0;
+    }
+
+    t[0] * 3600.0 + t[1] * 60.0 + t[2] + t[3] / fps
+}
+
+/// Create a media item. Only when neither duration nor out is known, the file gets probed.
+///
+/// With only out, the clip is at least this long, so `out - seek` is its length.
+fn media_item(source: &str, seek: f64, out: f64, duration: f64) -> Media {
+    let mut media = Media::new(0, source, duration <= 0.0 && out <= 0.0);
+
+    if duration > 0.0 {
+        media.duration = duration;
+    }
+
+    media.seek = seek;
+    media.out = if out > 0.0 { out } else { media.duration };
+
+    if media.duration < media.out {
+        media.duration = media.out;
+    }
+
+    media
+}
+
+fn parse_text(reader: BufReader<File>) -> Result<Vec<Media>, Error> {
+    let mut program = vec![];
+
+    for line in reader.lines() {
+        let line = line?;
+
+        if !line.starts_with('#') {
+            program.push(Media::new(0, &line, true));
+        }
+    }
+
+    Ok(program)
+}
+
+/// Parse extended m3u, with EXTINF duration/title and EXTVLCOPT start/stop time.
+fn parse_m3u(reader: BufReader<File>) -> Result<Vec<Media>, Error> {
+    let mut program = vec![];
+    let mut duration = 0.0;
+    let mut seek = 0.0;
+    let mut out = 0.0;
+    let mut title = String::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            let (attributes, name) = info.split_once(',').unwrap_or((info, ""));
+
+            duration = attributes
+                .split_whitespace()
+                .next()
+                .and_then(|d| d.parse().ok())
+                .unwrap_or(0.0);
+            title = name.trim().to_string();
+        } else if let Some(option) = line.strip_prefix("#EXTVLCOPT:") {
+            if let Some((key, value)) = option.split_once('=') {
+                match key.trim() {
+                    "start-time" => seek = parse_time(value),
+                    "stop-time" => out = parse_time(value),
+                    _ => (),
+                }
+            }
+        } else if !line.starts_with('#') {
+            let mut item = media_item(line, seek, out, duration);
+            item.title = mem::take(&mut title);
+            program.push(item);
+
+            duration = 0.0;
+            seek = 0.0;
+            out = 0.0;
+        }
+    }
+
+    Ok(program)
+}
+
+fn split_csv(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+
+    fields.push(field);
+
+    fields
+}
+
+/// Parse csv file.
+///
+/// When the first row contains a `source` column, it is used as header and defines the column order,
+/// otherwise the order is: source, in, out, category, title.
+/// Values are separated by comma or semicolon, times can be in seconds or in HH:MM:SS.
+fn parse_csv(reader: BufReader<File>) -> Result<Vec<Media>, Error> {
+    let mut program = vec![];
+    let mut columns: Vec<String> = ["source", "in", "out", "category", "title"]
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    let mut delimiter = None;
+
+    for line in reader.lines() {
+        let line = line?;
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let delim = *delimiter.get_or_insert_with(|| {
+            if line.matches(';').count() > line.matches(',').count() {
+                ';'
+            } else {
+                ','
+            }
+        });
+        let fields = split_csv(&line, delim);
+
+        if program.is_empty()
+            && fields
+                .iter()
+                .any(|f| f.trim().eq_ignore_ascii_case("source"))
+        {
+            columns = fields.iter().map(|f| f.trim().to_lowercase()).collect();
+            continue;
+        }
+
+        let value = |name: &str| {
+            columns
+                .iter()
+                .position(|c| c == name)
+                .and_then(|i| fields.get(i))
+                .map(|f| f.trim())
+                .unwrap_or_default()
+        };
+
+        if value("source").is_empty() {
+            continue;
+        }
+
+        let mut item = media_item(
+            value("source"),
+            parse_time(value("in")),
+            parse_time(value("out")),
+            parse_time(value("duration")),
+        );
+        item.category = value("category").to_string();
+        item.title = value("title").to_string();
+        item.description = value("description").to_string();
+
+        program.push(item);
+    }
+
+    Ok(program)
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Some(b) = std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+            {
+                decoded.push(b);
+                i += 3;
+                continue;
+            }
+        }
+
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn xml_tag(text: &str, tag: &str) -> Option<String> {
+    Regex::new(&format!(r"(?s)<{tag}(?:\s[^>]*)?>(.*?)</{tag}>"))
+        .ok()
+        .and_then(|r| r.captures(text))
+        .map(|c| xml_unescape(c[1].trim()))
+}
+
+/// Parse xspf playlist, track duration is in milliseconds.
+fn parse_xspf(content: &str) -> Vec<Media> {
+    let mut program = vec![];
+    let track_regex = Regex::new(r"(?s)<track>(.*?)</track>").unwrap();
+    let option_regex =
+        Regex::new(r"<vlc:option>(start-time|stop-time)=([^<]*)</vlc:option>").unwrap();
+
+    for track in track_regex.captures_iter(content) {
+        let track = &track[1];
+        let location = match xml_tag(track, "location") {
+            Some(l) => l,
+            None => continue,
+        };
+        let source = match location.strip_prefix("file://") {
+            Some(path) => percent_decode(path),
+            None => location,
+        };
+        let duration = xml_tag(track, "duration")
+            .and_then(|d| d.parse::<f64>().ok())
+            .map(|d| d / 1000.0)
+            .unwrap_or(0.0);
+        let mut seek = 0.0;
+        let mut out = 0.0;
+
+        for option in option_regex.captures_iter(track) {
+            match &option[1] {
+                "start-time" => seek = parse_time(&option[2]),
+                _ => out = parse_time(&option[2]),
+            }
+        }
+
+        let mut item = media_item(&source, seek, out, duration);
+        item.title = xml_tag(track, "title").unwrap_or_default();
+        item.description = xml_tag(track, "annotation").unwrap_or_default();
+
+        program.push(item);
+    }
+
+    program
+}
+
+/// Parse CMX3600 edit decision list.
+///
+/// Source in/out points are taken from the event lines, the file from the
+/// `* SOURCE FILE:` or `* FROM CLIP NAME:` comment, or the reel name as fallback.
+/// Black and aux events are skipped, when they have no clip name.
+fn parse_edl(reader: BufReader<File>, fps: f64) -> Result<Vec<Media>, Error> {
+    let mut program = vec![];
+    let mut events: Vec<(String, String, f64, f64)> = vec![];
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        let fields: Vec<&str> = line.split_whitespace().collect();
+
+        if let Some(file) = line.strip_prefix("* SOURCE FILE:") {
+            if let Some(event) = events.last_mut() {
+                event.1 = file.trim().to_string();
+            }
+        } else if let Some(clip) = line.strip_prefix("* FROM CLIP NAME:") {
+            if let Some(event) = events.last_mut() {
+                if event.1.is_empty() || event.1 == event.0 {
+                    event.1 = clip.trim().to_string();
+                }
+            }
+        } else if fields.len() >= 8 && fields[0].chars().all(|c| c.is_ascii_digit()) {
+            let reel = fields[1].to_string();
+            let n = fields.len();
+            let seek = timecode_to_sec(fields[n - 4], fps);
+            let out = timecode_to_sec(fields[n - 3], fps);
+            let source = if ["BL", "AX"].contains(&reel.as_str()) {
+                String::new()
+            } else {
+                reel.clone()
+            };
+
+            events.push((reel, source, seek, out));
+        }
+    }
+
+    for (_, source, seek, out) in events {
+        if !source.is_empty() && out > seek {
+            program.push(media_item(&source, seek, out, 0.0));
+        }
+    }
+
+    Ok(program)
+}
+
+/// Read the clips from the import file, without writing a playlist.
+pub fn import_program(
+    config: &PlayoutConfig,
+    path: &str,
+    format: Option<ImportFormat>,
+) -> Result<Vec<Media>, Error> {
+    let format = format.unwrap_or_else(|| ImportFormat::from_path(path));
+
+    if format == ImportFormat::Xspf {
+        return Ok(parse_xspf(&read_to_string(path)?));
+    }
+
+    let reader = BufReader::new(File::open(path)?);
+
+    match format {
+        ImportFormat::M3u => parse_m3u(reader),
+        ImportFormat::Csv => parse_csv(reader),
+        ImportFormat::Edl => parse_edl(reader, config.processing.fps),
+        _ => parse_text(reader),
+    }
+}
 
 pub fn import_file(
     config: &PlayoutConfig,
     date: &str,
     channel_name: Option<String>,
     path: &str,
+    format: Option<ImportFormat>,
 ) -> Result<String, Error> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
     let mut playlist = JsonPlaylist {
         channel: channel_name.unwrap_or_else(|| "Channel 1".to_string()),
         date: date.to_string(),
@@ -44,15 +434,7 @@ pub fn import_file(
 
     create_dir_all(playlist_path)?;
 
-    for line in reader.lines() {
-        let line = line?;
-
-        if !line.starts_with('#') {
-            let item = Media::new(0, &line, true);
-
-            playlist.program.push(item);
-        }
-    }
+    playlist.program = import_program(config, path, format)?;
 
     let mut file_exists = false;
 
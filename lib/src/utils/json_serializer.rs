@@ -1,7 +1,7 @@
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize};
 use std::{
     fs::File,
-    path::Path,
+    path::{Path, PathBuf},
     sync::{atomic::AtomicBool, Arc},
     thread,
 };
@@ -9,8 +9,9 @@ use std::{
 use simplelog::*;
 
 use crate::utils::{
-    controller::ProcessUnit::*, get_date, is_remote, modified_time, time_from_header,
-    validate_playlist, Media, PlayoutConfig, DUMMY_LEN,
+    controller::ProcessUnit::*, fetch_remote_playlist, get_date, is_remote, json_reader,
+    modified_time, set_day_times, transition_overlap, validate_playlist, FrameRate, FrameTime,
+    Media, Overlap, PlayoutConfig, DUMMY_LEN,
 };
 
 /// This is our main playlist object, it holds all necessary information for the current day.
@@ -18,6 +19,7 @@ use crate::utils::{
 pub struct JsonPlaylist {
     #[serde(default = "default_channel")]
     pub channel: String,
+    #[serde(default)]
     pub date: String,
 
     #[serde(skip_serializing, skip_deserializing)]
@@ -29,6 +31,7 @@ pub struct JsonPlaylist {
     #[serde(skip_serializing, skip_deserializing)]
     pub modified: Option<String>,
 
+    #[serde(deserialize_with = "deserialize_program")]
     pub program: Vec<Media>,
 }
 
@@ -61,37 +64,234 @@ fn default_channel() -> String {
     "Channel 1".to_string()
 }
 
+/// Only block references can leave out `in`, `out` and `duration`,
+/// clips need them all, like before blocks existed.
+fn deserialize_program<'de, D>(deserializer: D) -> Result<Vec<Media>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let items: Vec<serde_json::Value> = Deserialize::deserialize(deserializer)?;
+    let mut program = vec![];
+
+    for (i, value) in items.into_iter().enumerate() {
+        let missing: Vec<&str> = ["in", "out", "duration"]
+            .into_iter()
+            .filter(|key| value.get(key).is_none())
+            .collect();
+        let item: Media = serde_json::from_value(value).map_err(de::Error::custom)?;
+
+        if !missing.is_empty() && !item.is_block() {
+            return Err(de::Error::custom(format!(
+                "program item {i} ({}) misses field: {}",
+                item.source,
+                missing.join(", ")
+            )));
+        }
+
+        program.push(item);
+    }
+
+    Ok(program)
+}
+
+/// Get path from block, relative paths are based on the playlist root.
+fn block_path(config: &PlayoutConfig, source: &str) -> PathBuf {
+    let path = Path::new(source);
+
+    if path.is_absolute() {
+        return path.to_owned();
+    }
+
+    let mut root = Path::new(&config.playlist.path);
+
+    if root.is_file() {
+        root = root.parent().unwrap_or(root);
+    }
+
+    root.join(path)
+}
+
+fn expand_program(
+    config: &PlayoutConfig,
+    program: Vec<Media>,
+    parents: &mut Vec<PathBuf>,
+) -> Vec<Media> {
+    let mut new_program = vec![];
+
+    for mut item in program {
+        if !item.is_block() {
+            new_program.push(item);
+            continue;
+        }
+
+        let path = block_path(config, &item.source);
+
+        if parents.contains(&path) {
+            error!(
+                "Block <b><magenta>{}</></b> includes itself, skip it!",
+                path.display()
+            );
+            continue;
+        }
+
+        match json_reader(&path) {
+            Ok(block) => {
+                parents.push(path);
+                let mut block_program = expand_program(config, block.program, parents);
+                parents.pop();
+
+                if let Some(first) = block_program.first_mut() {
+                    if item.start.is_some() {
+                        first.start = item.start.clone();
+                    }
+                }
+
+                new_program.append(&mut block_program);
+            }
+            Err(e) if item.out > item.seek => {
+                // block reference has a length, fill it with filler
+                error!(
+                    "Block <b><magenta>{}</></b> not readable, fill its length with filler: {e}",
+                    path.display()
+                );
+                item.source = String::new();
+                item.duration = item.duration.max(item.out);
+                new_program.push(item);
+            }
+            Err(e) => {
+                error!(
+                    "Block <b><magenta>{}</></b> not readable and has no length, following clips start earlier: {e}",
+                    path.display()
+                );
+            }
+        }
+    }
+
+    new_program
+}
+
+/// Replace all block items (references to other playlist files)
+/// with the program from this block.
+pub fn expand_blocks(config: &PlayoutConfig, program: Vec<Media>) -> Vec<Media> {
+    expand_program(config, program, &mut vec![])
+}
+
 fn set_defaults(
+    config: &PlayoutConfig,
     mut playlist: JsonPlaylist,
     current_file: String,
-    mut start_sec: f64,
+    start_sec: f64,
 ) -> JsonPlaylist {
+    let rate = FrameRate::from_fps(config.processing.fps);
+    let date = playlist.date.clone();
+    let mut begin = FrameTime::from_sec(start_sec, rate);
+    let mut program: Vec<Media> = vec![];
+
     playlist.current_file = Some(current_file);
     playlist.start_sec = Some(start_sec);
 
     // Add extra values to every media clip
-    for (i, item) in playlist.program.iter_mut().enumerate() {
-        item.begin = Some(start_sec);
-        item.index = Some(i);
+    for mut item in playlist.program {
+        // hard start is a wall time, convert it to the real seconds from playlist day
+        if let Some(target) = item
+            .hard_start_sec(config, &date)
+            .map(|t| FrameTime::from_sec(t, rate))
+        {
+            if target < begin {
+                // cut previous clips, to hit the fixed start time
+                let mut overlap = begin - target;
+
+                while overlap > FrameTime::zero(rate) {
+                    match program.last_mut() {
+                        Some(last) if last.frame_length(rate) > overlap => {
+                            last.out = (FrameTime::from_sec(last.out, rate) - overlap).sec();
+                            overlap = FrameTime::zero(rate);
+                        }
+                        Some(last) => {
+                            overlap -= last.frame_length(rate);
+                            program.pop();
+                        }
+                        None => break,
+                    }
+                }
+
+                begin = target;
+            } else if target > begin {
+                // fill gap until fixed start time, gen_source will take the filler for it
+                let mut filler = Media::new(0, "", false);
+                filler.begin = Some(begin.sec());
+                filler.duration = (target - begin).sec();
+                filler.out = filler.duration;
+                program.push(filler);
+
+                begin = target;
+            }
+        }
+
+        item.overlap = None;
+        item.next_overlap = Some(false);
+
+        // transition: the previous clip ends earlier and its tail gets blended into this clip
+        if let Some(last) = program.last_mut() {
+            if let Some((transition, overlap)) = transition_overlap(config, last, &item, rate) {
+                last.out = (FrameTime::from_sec(last.out, rate) - overlap).sec();
+                last.next_overlap = Some(true);
+                item.overlap = Some(Overlap {
+                    kind: transition.kind,
+                    source: last.source.clone(),
+                    seek: last.out,
+                    duration: overlap.sec(),
+                    audio_streams: None,
+                });
+
+                begin -= overlap;
+            }
+        }
+
+        item.begin = Some(begin.sec());
         item.last_ad = Some(false);
         item.next_ad = Some(false);
         item.process = Some(true);
         item.filter = None;
 
-        start_sec += item.out - item.seek;
+        // count in frames, so begin not drift over the day
+        begin += item.frame_length(rate);
+        program.push(item);
     }
 
+    for (i, item) in program.iter_mut().enumerate() {
+        item.index = Some(i);
+    }
+
+    playlist.program = program;
+
     playlist
 }
 
+/// Expand blocks and set the start time from every clip, like the playout does it.
+///
+/// Hard start times cut the clips before, or get reached with a filler, and transitions
+/// let clips overlap. Export and program guides use this, so they show what really plays.
+pub fn timed_playlist(config: &PlayoutConfig, mut playlist: JsonPlaylist) -> JsonPlaylist {
+    let mut day_config = config.clone();
+    set_day_times(&mut day_config, &playlist.date);
+
+    let start_sec = day_config.playlist.start_sec.unwrap_or(0.0);
+    let current_file = playlist.current_file.clone().unwrap_or_default();
+    playlist.program = expand_blocks(config, playlist.program);
+
+    set_defaults(&day_config, playlist, current_file, start_sec)
+}
+
 fn loop_playlist(
     config: &PlayoutConfig,
     current_file: String,
     mut playlist: JsonPlaylist,
 ) -> JsonPlaylist {
+    let rate = FrameRate::from_fps(config.processing.fps);
     let start_sec = config.playlist.start_sec.unwrap();
-    let mut begin = start_sec;
-    let length = config.playlist.length_sec.unwrap();
+    let mut begin = FrameTime::from_sec(start_sec, rate);
+    let end = begin + FrameTime::from_sec(config.playlist.length_sec.unwrap(), rate);
     let mut program_list = vec![];
     let mut index = 0;
 
@@ -102,30 +302,23 @@ fn loop_playlist(
         for item in playlist.program.iter() {
             let media = Media {
                 index: Some(index),
-                begin: Some(begin),
-                seek: item.seek,
-                out: item.out,
-                duration: item.duration,
-                category: item.category.clone(),
-                source: item.source.clone(),
-                audio: item.audio.clone(),
-                cmd: item.cmd.clone(),
-                probe: item.probe.clone(),
+                begin: Some(begin.sec()),
                 process: Some(true),
                 unit: Decoder,
                 last_ad: Some(false),
                 next_ad: Some(false),
                 filter: None,
                 custom_filter: String::new(),
+                ..item.clone()
             };
 
-            if begin < start_sec + length {
+            if begin < end {
                 program_list.push(media);
             } else {
                 break 'program_looper;
             }
 
-            begin += item.out - item.seek;
+            begin += item.frame_length(rate);
             index += 1;
         }
     }
@@ -146,8 +339,10 @@ pub fn read_json(
 ) -> JsonPlaylist {
     let config_clone = config.clone();
     let mut playlist_path = Path::new(&config.playlist.path).to_owned();
-    let start_sec = config.playlist.start_sec.unwrap();
-    let date = get_date(seek, start_sec, next_start);
+    let date = get_date(seek, config.playlist.start_sec.unwrap(), next_start);
+    let mut day_config = config.clone();
+    set_day_times(&mut day_config, &date);
+    let start_sec = day_config.playlist.start_sec.unwrap();
 
     if playlist_path.is_dir() || is_remote(&config.playlist.path) {
         let d: Vec<&str> = date.split('-').collect();
@@ -166,31 +361,23 @@ pub fn read_json(
     }
 
     if is_remote(&current_file) {
-        let response = reqwest::blocking::Client::new().get(&current_file).send();
-
-        if let Ok(resp) = response {
-            if resp.status().is_success() {
-                let headers = resp.headers().clone();
+        if let Some((mut playlist, modified)) = fetch_remote_playlist(config, &current_file) {
+            playlist.program = expand_blocks(config, playlist.program);
 
-                if let Ok(body) = resp.text() {
-                    let mut playlist: JsonPlaylist =
-                        serde_json::from_str(&body).expect("Could't read remote json playlist.");
+            // catch empty program list
+            if playlist.program.is_empty() {
+                playlist = JsonPlaylist::new(date, start_sec)
+            }
 
-                    if let Some(time) = time_from_header(&headers) {
-                        playlist.modified = Some(time.to_string());
-                    }
+            playlist.modified = modified;
 
-                    let list_clone = playlist.clone();
+            let list_clone = playlist.clone();
 
-                    thread::spawn(move || {
-                        validate_playlist(list_clone, is_terminated, config_clone)
-                    });
+            thread::spawn(move || validate_playlist(list_clone, is_terminated, config_clone));
 
-                    match config.playlist.infinit {
-                        true => return loop_playlist(config, current_file, playlist),
-                        false => return set_defaults(playlist, current_file, start_sec),
-                    }
-                }
+            match config.playlist.infinit {
+                true => return loop_playlist(&day_config, current_file, playlist),
+                false => return set_defaults(&day_config, playlist, current_file, start_sec),
             }
         }
     } else if playlist_path.is_file() {
@@ -207,6 +394,8 @@ pub fn read_json(
             }
         };
 
+        playlist.program = expand_blocks(config, playlist.program);
+
         // catch empty program list
         if playlist.program.is_empty() {
             playlist = JsonPlaylist::new(date, start_sec)
@@ -219,8 +408,8 @@ pub fn read_json(
         thread::spawn(move || validate_playlist(list_clone, is_terminated, config_clone));
 
         match config.playlist.infinit {
-            true => return loop_playlist(config, current_file, playlist),
-            false => return set_defaults(playlist, current_file, start_sec),
+            true => return loop_playlist(&day_config, current_file, playlist),
+            false => return set_defaults(&day_config, playlist, current_file, start_sec),
         }
     }
 
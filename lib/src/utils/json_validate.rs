@@ -10,8 +10,9 @@ use std::{
 use simplelog::*;
 
 use crate::utils::{
-    loop_image, sec_to_time, seek_and_length, valid_source, vec_strings, JsonPlaylist, Media,
-    OutputMode::Null, PlayoutConfig, FFMPEG_IGNORE_ERRORS, IMAGE_FORMAT,
+    loop_image, sec_to_time, seek_and_length, set_day_times, transition_overlap, valid_source,
+    vec_strings, FrameRate, FrameTime, JsonPlaylist, Media, OutputMode::Null, PlayoutConfig,
+    FFMPEG_IGNORE_ERRORS, IMAGE_FORMAT,
 };
 
 /// check if ffmpeg can read the file and apply filter to it.
@@ -130,10 +131,13 @@ pub fn validate_playlist(
         config.text.add_text = false;
     }
 
-    let mut length = config.playlist.length_sec.unwrap();
-    let mut begin = config.playlist.start_sec.unwrap();
+    // same day times like in playout, on DST change days the day is 23 or 25 hours long
+    set_day_times(&mut config, &date);
 
-    length += begin;
+    let rate = FrameRate::from_fps(config.processing.fps);
+    let day_start = config.playlist.start_sec.unwrap();
+    let mut begin = FrameTime::from_sec(day_start, rate);
+    let length = begin + FrameTime::from_sec(config.playlist.length_sec.unwrap(), rate);
 
     debug!("Validate playlist from: <yellow>{date}</>");
 
@@ -144,25 +148,60 @@ pub fn validate_playlist(
 
         let pos = index + 1;
 
-        if valid_source(&item.source) {
-            if let Err(e) = check_media(item.clone(), pos, begin, &config) {
+        if let Some(target) = item
+            .hard_start_sec(&config, &date)
+            .map(|t| FrameTime::from_sec(t, rate))
+        {
+            if target < begin {
+                warn!(
+                    "<bright black>[Validator]</> Fixed start on position <yellow>{pos}</> {}, cut <yellow>{:.3}</> seconds from previous clip(s)",
+                    sec_to_time(target.sec()),
+                    (begin - target).sec()
+                );
+            } else if target > begin {
+                info!(
+                    "<bright black>[Validator]</> Fixed start on position <yellow>{pos}</> {}, fill gap of <yellow>{:.3}</> seconds",
+                    sec_to_time(target.sec()),
+                    (target - begin).sec()
+                );
+            }
+
+            begin = target;
+        }
+
+        // clips with transition overlap with the previous one
+        if let Some((_, overlap)) = index
+            .checked_sub(1)
+            .and_then(|i| transition_overlap(&config, &playlist.program[i], item, rate))
+        {
+            begin -= overlap;
+        }
+
+        if item.is_live() {
+            debug!(
+                "<bright black>[Validator]</> Live stream on position <yellow>{pos}</> {}: <b><magenta>{}</></b>",
+                sec_to_time(begin.sec()),
+                item.source
+            );
+        } else if valid_source(&item.source) {
+            if let Err(e) = check_media(item.clone(), pos, begin.sec(), &config) {
                 error!("{e}");
             };
         } else {
             error!(
                 "Source on position <yellow>{pos}</> {} not exists: <b><magenta>\"{}\"</></b>",
-                sec_to_time(begin),
+                sec_to_time(begin.sec()),
                 item.source
             );
         }
 
-        begin += item.out - item.seek;
+        begin += item.frame_length(rate);
     }
 
-    if !config.playlist.infinit && length > begin + 1.0 {
+    if !config.playlist.infinit && length > begin + FrameTime::from_sec(1.0, rate) {
         error!(
             "Playlist from <yellow>{date}</> not long enough, <yellow>{}</> needed!",
-            sec_to_time(length - begin),
+            sec_to_time((length - begin).sec()),
         );
     }
 
@@ -1,4 +1,5 @@
 use std::{
+    collections::BTreeMap,
     ffi::OsStr,
     fs::{self, metadata, File},
     io::{BufRead, BufReader, Error},
@@ -13,6 +14,7 @@ use std::{
 use std::env;
 
 use chrono::{prelude::*, Duration};
+use chrono_tz::Tz;
 use ffprobe::{ffprobe, Format, Stream};
 use jsonrpc_http_server::hyper::HeaderMap;
 use rand::prelude::*;
@@ -22,39 +24,86 @@ use serde::{de::Deserializer, Deserialize, Serialize};
 use serde_json::json;
 use simplelog::*;
 
+mod clock;
 pub mod config;
 pub mod controller;
+pub mod epg;
+pub mod export;
+mod filler;
 pub mod folder;
+mod frame_time;
 mod generator;
 pub mod import;
 pub mod json_serializer;
 mod json_validate;
 mod logging;
+mod playlist_cache;
+mod rotation;
+mod scte35;
+mod shuffle;
+mod time_zone;
 
 #[cfg(windows)]
 mod windows;
 
+pub use clock::{clock, look_ahead, set_clock, with_look_ahead, Clock};
 pub use config::{
-    self as playout_config,
+    self as playout_config, ClockMode,
     OutputMode::{self, *},
     PlayoutConfig,
     ProcessMode::{self, *},
-    DUMMY_LEN, FFMPEG_IGNORE_ERRORS, IMAGE_FORMAT,
+    RotationRule, DUMMY_LEN, FFMPEG_IGNORE_ERRORS, IMAGE_FORMAT,
 };
 pub use controller::{
     PlayerControl, PlayoutStatus, ProcessControl,
     ProcessUnit::{self, *},
+    Supervisor,
 };
-pub use generator::generate_playlist;
-pub use json_serializer::{read_json, JsonPlaylist};
+pub use filler::{best_fit, fill_gap, is_filler_pool};
+pub use frame_time::{FrameRate, FrameTime};
+pub use generator::{generate_playlist, TemplateSlot, WeeklyTemplate};
+pub use json_serializer::{expand_blocks, read_json, timed_playlist, JsonPlaylist};
 pub use json_validate::validate_playlist;
 pub use logging::{init_logging, send_mail};
+pub use playlist_cache::{fetch_remote_playlist, prefetch_remote_playlist};
+pub use rotation::Rotation;
+pub use scte35::{crc32_mpeg2, SpliceInsert};
+pub use shuffle::{no_repeat_order, shuffle_clips, HistoryEntry, PlayHistory};
+pub use time_zone::{
+    broadcast_day, channel_instant, channel_time, day_length, parse_time_zone, real_sec,
+    sec_since_midnight, set_time_zone, time_zone,
+};
 
 use crate::{
     filter::{filter_chains, Filters},
     vec_strings,
 };
 
+/// Transition into a clip, from the end of the previous clip.
+///
+/// `type` is a transition from the ffmpeg xfade filter, like fade, dissolve or wipeleft.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Transition {
+    #[serde(rename = "type", default = "default_transition")]
+    pub kind: String,
+    pub duration: f64,
+}
+
+fn default_transition() -> String {
+    "fade".to_string()
+}
+
+/// Tail from the previous clip, which gets blended into the begin of the current clip.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlap {
+    pub kind: String,
+    pub source: String,
+    pub seek: f64,
+    pub duration: f64,
+    /// Audio streams from the previous clip, known after it was probed.
+    pub audio_streams: Option<usize>,
+}
+
 /// Video clip struct to hold some important states and comments for current media.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Media {
@@ -63,9 +112,11 @@ pub struct Media {
 
     #[serde(skip_serializing, skip_deserializing)]
     pub index: Option<usize>,
-    #[serde(rename = "in")]
+    #[serde(rename = "in", default)]
     pub seek: f64,
+    #[serde(default)]
     pub out: f64,
+    #[serde(default)]
     pub duration: f64,
 
     #[serde(
@@ -77,6 +128,14 @@ pub struct Media {
     #[serde(deserialize_with = "null_string")]
     pub source: String,
 
+    #[serde(
+        rename = "type",
+        default,
+        deserialize_with = "null_string",
+        skip_serializing_if = "is_empty_string"
+    )]
+    pub item_type: String,
+
     #[serde(
         default,
         deserialize_with = "null_string",
@@ -93,6 +152,55 @@ pub struct Media {
     #[serde(default, skip_serializing_if = "is_empty_string")]
     pub custom_filter: String,
 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+
+    #[serde(
+        default,
+        deserialize_with = "null_string",
+        skip_serializing_if = "is_empty_string"
+    )]
+    pub title: String,
+
+    #[serde(
+        default,
+        deserialize_with = "null_string",
+        skip_serializing_if = "is_empty_string"
+    )]
+    pub subtitle: String,
+
+    #[serde(
+        default,
+        deserialize_with = "null_string",
+        skip_serializing_if = "is_empty_string"
+    )]
+    pub description: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub episode: Option<i32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub season: Option<i32>,
+
+    #[serde(
+        default,
+        deserialize_with = "null_string",
+        skip_serializing_if = "is_empty_string"
+    )]
+    pub external_id: String,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transition: Option<Transition>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub overlap: Option<Overlap>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub next_overlap: Option<bool>,
+
     #[serde(skip_serializing, skip_deserializing)]
     pub probe: Option<MediaProbe>,
 
@@ -105,6 +213,9 @@ pub struct Media {
     #[serde(skip_serializing, skip_deserializing)]
     pub process: Option<bool>,
 
+    #[serde(skip_serializing, skip_deserializing)]
+    pub filler_queue: Vec<Media>,
+
     #[serde(default, skip_serializing)]
     pub unit: ProcessUnit,
 }
@@ -134,20 +245,34 @@ impl Media {
             duration,
             category: String::new(),
             source: src.to_string(),
+            item_type: String::new(),
             audio: String::new(),
             cmd: Some(vec_strings!["-i", src]),
             filter: None,
             custom_filter: String::new(),
+            start: None,
+            title: String::new(),
+            subtitle: String::new(),
+            description: String::new(),
+            episode: None,
+            season: None,
+            external_id: String::new(),
+            metadata: BTreeMap::new(),
+            transition: None,
+            overlap: None,
+            next_overlap: Some(false),
             probe,
             last_ad: Some(false),
             next_ad: Some(false),
             process: Some(true),
+            filler_queue: vec![],
             unit: Decoder,
         }
     }
 
     pub fn add_probe(&mut self) {
-        if self.probe.is_none() {
+        // live streams are not probed, it would block until the stream is available
+        if self.probe.is_none() && !self.is_live() {
             let probe = MediaProbe::new(&self.source);
             self.probe = Some(probe.clone());
 
@@ -174,6 +299,141 @@ impl Media {
         let mut node = self.clone();
         self.filter = Some(filter_chains(config, &mut node, filter_chain))
     }
+
+    /// Check if the item is a scheduled live stream.
+    pub fn is_live(&self) -> bool {
+        self.item_type == "live"
+    }
+
+    /// Check if the item is a reference to a playlist block.
+    pub fn is_block(&self) -> bool {
+        !is_remote(&self.source)
+            && file_extension(Path::new(&self.source))
+                .filter(|e| e.to_lowercase() == "json")
+                .is_some()
+    }
+
+    /// Get the fixed (hard) start time in seconds, when one is set.
+    ///
+    /// Times before the playlist day start belong to the next calendar day.
+    /// Invalid times get ignored, with a warning.
+    pub fn hard_start(&self, day_start: f64) -> Option<f64> {
+        let start = self.start.as_ref()?;
+
+        if !is_time_str(start) {
+            warn!(
+                "Start time <yellow>{start}</> is not valid, ignore it: <b><magenta>{}</></b>",
+                self.source
+            );
+
+            return None;
+        }
+
+        let sec = time_to_sec(start);
+
+        if sec < day_start {
+            Some(sec + 86400.0)
+        } else {
+            Some(sec)
+        }
+    }
+
+    /// Hard start in real seconds from the playlist day, like the playout plays it.
+    ///
+    /// `config` needs the day times from the playlist date, see [set_day_times].
+    pub fn hard_start_sec(&self, config: &PlayoutConfig, date: &str) -> Option<f64> {
+        let day_start = match config.playlist.day_start.contains(':') {
+            true => time_to_sec(&config.playlist.day_start),
+            false => config.playlist.start_sec.unwrap_or(0.0),
+        };
+
+        self.hard_start(day_start).map(|t| day_sec(config, date, t))
+    }
+
+    /// Length from seek to out, in whole frames.
+    pub fn frame_length(&self, rate: FrameRate) -> FrameTime {
+        FrameTime::from_sec(self.out, rate) - FrameTime::from_sec(self.seek, rate)
+    }
+
+    /// Transition into this clip, from playlist item, or from its category in config.
+    pub fn get_transition(&self, config: &PlayoutConfig) -> Option<Transition> {
+        self.transition
+            .clone()
+            .or_else(|| config.processing.transitions.get(&self.category).cloned())
+            .filter(|t| t.duration > 0.0)
+    }
+
+    /// Take the streams for the overlap from the probe of the previous clip.
+    pub fn carry_overlap(&mut self, last: &Media) {
+        if let Some(overlap) = self
+            .overlap
+            .as_mut()
+            .filter(|o| o.source == last.source && o.audio_streams.is_none())
+        {
+            if let Some(probe) = last.probe.as_ref().filter(|p| !p.video_streams.is_empty()) {
+                overlap.audio_streams = Some(probe.audio_streams.len());
+            }
+        }
+    }
+
+    /// Drop the overlap, when the tail from the previous clip is not usable.
+    pub fn check_overlap(&mut self) {
+        if let Some(overlap) = &self.overlap {
+            if overlap.audio_streams.is_none() || !valid_source(&overlap.source) {
+                warn!(
+                    "Transition source not usable, skip: <b><magenta>{}</></b>",
+                    overlap.source
+                );
+                self.overlap = None;
+            }
+        }
+    }
+}
+
+fn can_blend(media: &Media) -> bool {
+    !media.source.is_empty()
+        && media.audio.is_empty()
+        && !media.is_block()
+        && !media.is_live()
+        && media
+            .source
+            .rsplit_once('.')
+            .map(|(_, e)| e.to_lowercase())
+            .filter(|c| IMAGE_FORMAT.contains(&c.as_str()))
+            .is_none()
+}
+
+/// Get transition and overlap between two clips, in whole frames.
+///
+/// Clips with fixed start time, separate audio file, or images as previous clip are not blended.
+/// The overlap is at most the half length from each clip.
+pub fn transition_overlap(
+    config: &PlayoutConfig,
+    last: &Media,
+    item: &Media,
+    rate: FrameRate,
+) -> Option<(Transition, FrameTime)> {
+    let transition = item.get_transition(config)?;
+
+    if item.hard_start(0.0).is_some()
+        || !can_blend(last)
+        || !item.audio.is_empty()
+        || item.source.is_empty()
+        || item.is_live()
+    {
+        return None;
+    }
+
+    let duration = transition
+        .duration
+        .min((last.out - last.seek) / 2.0)
+        .min((item.out - item.seek) / 2.0);
+    let overlap = FrameTime::from_sec(duration, rate);
+
+    match overlap > FrameTime::zero(rate) {
+        true => Some((transition, overlap)),
+        false => None,
+    }
 }
 
 impl PartialEq for Media {
@@ -182,9 +442,19 @@ impl PartialEq for Media {
             && self.out == other.out
             && self.duration == other.duration
             && self.source == other.source
+            && self.item_type == other.item_type
             && self.category == other.category
             && self.audio == other.audio
             && self.custom_filter == other.custom_filter
+            && self.start == other.start
+            && self.title == other.title
+            && self.subtitle == other.subtitle
+            && self.description == other.description
+            && self.episode == other.episode
+            && self.season == other.season
+            && self.external_id == other.external_id
+            && self.metadata == other.metadata
+            && self.transition == other.transition
     }
 }
 
@@ -300,6 +570,16 @@ pub fn write_status(config: &PlayoutConfig, date: &str, shift: f64) {
     };
 }
 
+/// File beside the status file, for state which should survive a restart.
+///
+/// Returns None when generating playlists, then nothing gets saved.
+pub fn state_file(config: &PlayoutConfig, extension: &str) -> Option<PathBuf> {
+    match config.general.generate.is_none() && !config.general.stat_file.is_empty() {
+        true => Some(Path::new(&config.general.stat_file).with_extension(extension)),
+        false => None,
+    }
+}
+
 // pub fn get_timestamp() -> i32 {
 //     let local: DateTime<Local> = time_now();
 
@@ -308,10 +588,14 @@ pub fn write_status(config: &PlayoutConfig, date: &str, shift: f64) {
 
 /// Get current time in seconds.
 pub fn get_sec() -> f64 {
-    let local: DateTime<Local> = time_now();
+    sec_since_midnight(time_zone(), time_now().with_timezone(&Utc))
+}
 
-    (local.hour() * 3600 + local.minute() * 60 + local.second()) as f64
-        + (local.nanosecond() as f64 / 1000000000.0)
+/// Get current date in channel time zone, with offset in days.
+fn channel_date(days: i64) -> NaiveDate {
+    let time = channel_time(time_zone(), time_now().with_timezone(&Utc));
+
+    time.naive_local().date() + Duration::days(days)
 }
 
 /// Get current date for playlist, but check time with conditions:
@@ -319,24 +603,73 @@ pub fn get_sec() -> f64 {
 /// - When time is before playlist start, get date from yesterday.
 /// - When given next_start is over target length (normally a full day), get date from tomorrow.
 pub fn get_date(seek: bool, start: f64, next_start: f64) -> String {
-    let local: DateTime<Local> = time_now();
-
     if seek && start > get_sec() {
-        return (local - Duration::days(1)).format("%Y-%m-%d").to_string();
+        return channel_date(-1).format("%Y-%m-%d").to_string();
+    }
+
+    if start == 0.0 && next_start >= day_length(time_zone(), channel_date(0)) {
+        return channel_date(1).format("%Y-%m-%d").to_string();
+    }
+
+    channel_date(0).format("%Y-%m-%d").to_string()
+}
+
+/// Playlist length is a full day, when it is not set or 24 hours.
+pub fn is_full_day(config: &PlayoutConfig) -> bool {
+    !config.playlist.length.contains(':') || time_to_sec(&config.playlist.length) == 86400.0
+}
+
+/// Set start and length from the broadcast day at given date.
+///
+/// Both are in real seconds since midnight, so on a day with DST change
+/// a full day is 23 or 25 hours long.
+pub fn set_day_times(config: &mut PlayoutConfig, date: &str) {
+    if !config.playlist.day_start.contains(':') {
+        return;
     }
 
-    if start == 0.0 && next_start >= 86400.0 {
-        return (local + Duration::days(1)).format("%Y-%m-%d").to_string();
+    if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        let (start, length) = broadcast_day(
+            channel_zone(config),
+            date,
+            time_to_sec(&config.playlist.day_start),
+        );
+
+        config.playlist.start_sec = Some(start);
+
+        if is_full_day(config) {
+            config.playlist.length_sec = Some(length);
+        }
+    }
+}
+
+/// Seconds to add to a time after midnight, which belongs to the playlist from yesterday.
+pub fn day_offset(config: &PlayoutConfig) -> f64 {
+    match is_full_day(config) {
+        true => day_length(time_zone(), channel_date(-1)),
+        false => config.playlist.length_sec.unwrap_or(86400.0),
     }
+}
 
-    local.format("%Y-%m-%d").to_string()
+/// Time zone from the channel config.
+///
+/// The API works with many channels, so it can't use the time zone from the running one.
+pub fn channel_zone(config: &PlayoutConfig) -> Option<Tz> {
+    parse_time_zone(&config.playlist.timezone).unwrap_or_else(|_| time_zone())
+}
+
+/// Convert wall time from playlist date to real seconds since midnight.
+pub fn day_sec(config: &PlayoutConfig, date: &str, wall: f64) -> f64 {
+    match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        Ok(date) => real_sec(channel_zone(config), date, wall),
+        Err(_) => wall,
+    }
 }
 
 pub fn time_from_header(headers: &HeaderMap) -> Option<DateTime<Local>> {
     if let Some(time) = headers.get(header::LAST_MODIFIED) {
-        if let Ok(t) = time.to_str() {
-            let time = DateTime::parse_from_rfc2822(t);
-            let date_time: DateTime<Local> = time.unwrap().into();
+        if let Ok(Ok(t)) = time.to_str().map(DateTime::parse_from_rfc2822) {
+            let date_time: DateTime<Local> = t.into();
             return Some(date_time);
         };
     }
@@ -344,6 +677,19 @@ pub fn time_from_header(headers: &HeaderMap) -> Option<DateTime<Local>> {
     None
 }
 
+/// Get modification marker from response headers,
+/// this is the Last-Modified time, or the ETag when no time is given.
+pub fn modified_from_header(headers: &HeaderMap) -> Option<String> {
+    if let Some(time) = time_from_header(headers) {
+        return Some(time.to_string());
+    }
+
+    headers
+        .get(header::ETAG)
+        .and_then(|e| e.to_str().ok())
+        .map(|e| e.to_string())
+}
+
 /// Get file modification time.
 pub fn modified_time(path: &str) -> Option<String> {
     if is_remote(path) {
@@ -351,9 +697,7 @@ pub fn modified_time(path: &str) -> Option<String> {
 
         if let Ok(resp) = response {
             if resp.status().is_success() {
-                if let Some(time) = time_from_header(resp.headers()) {
-                    return Some(time.to_string());
-                }
+                return modified_from_header(resp.headers());
             }
         }
 
@@ -368,6 +712,15 @@ pub fn modified_time(path: &str) -> Option<String> {
     None
 }
 
+/// Check if string is a time like `hh:mm:ss`, which [time_to_sec] can convert.
+pub fn is_time_str(time: &str) -> bool {
+    time.split(':').count() == 3
+        && time.split(':').all(|t| {
+            t.parse::<f64>()
+                .map_or(false, |v| v.is_finite() && v >= 0.0)
+        })
+}
+
 /// Convert a formatted time string to seconds.
 pub fn time_to_sec(time_str: &str) -> f64 {
     if ["now", "", "none"].contains(&time_str) || !time_str.contains(':') {
@@ -413,22 +766,23 @@ pub fn is_close(a: f64, b: f64, to: f64) -> bool {
 pub fn get_delta(config: &PlayoutConfig, begin: &f64) -> (f64, f64) {
     let mut current_time = get_sec();
     let start = config.playlist.start_sec.unwrap();
-    let length = time_to_sec(&config.playlist.length);
-    let mut target_length = 86400.0;
-
-    if length > 0.0 && length != target_length {
-        target_length = length
-    }
-    if begin == &start && start == 0.0 && 86400.0 - current_time < 4.0 {
-        current_time -= target_length
+    let target_length = config.playlist.length_sec.unwrap_or(86400.0);
+    let today_length = day_length(time_zone(), channel_date(0));
+    let last_length = day_length(time_zone(), channel_date(-1));
+
+    if begin == &start && start == 0.0 && today_length - current_time < 4.0 {
+        current_time -= match is_full_day(config) {
+            true => today_length,
+            false => target_length,
+        }
     } else if start >= current_time && begin != &start {
-        current_time += target_length
+        current_time += day_offset(config)
     }
 
     let mut current_delta = begin - current_time;
 
-    if is_close(current_delta, 86400.0, config.general.stop_threshold) {
-        current_delta -= 86400.0
+    if is_close(current_delta, last_length, config.general.stop_threshold) {
+        current_delta -= last_length
     }
 
     let total_delta = if current_time < start {
@@ -443,7 +797,10 @@ pub fn get_delta(config: &PlayoutConfig, begin: &f64) -> (f64, f64) {
 /// Check if clip in playlist is in sync with global time.
 pub fn check_sync(config: &PlayoutConfig, delta: f64) -> bool {
     if delta.abs() > config.general.stop_threshold && config.general.stop_threshold > 0.0 {
-        error!("Clip begin out of sync for <yellow>{delta:.3}</> seconds. Stop playout!");
+        if config.general.stop_on_desync {
+            error!("Clip begin out of sync for <yellow>{delta:.3}</> seconds. Stop playout!");
+        }
+
         return false;
     }
 
@@ -468,11 +825,39 @@ pub fn loop_image(node: &Media) -> Vec<String> {
         source_cmd.append(&mut vec_strings!["-i", node.audio.clone()]);
     }
 
+    source_cmd.append(&mut overlap_input(node));
     source_cmd.append(&mut vec_strings!["-t", duration]);
 
     source_cmd
 }
 
+/// Input for the tail from the previous clip, when the clip begins with a transition.
+fn overlap_input(node: &Media) -> Vec<String> {
+    match &node.overlap {
+        Some(overlap) => vec_strings![
+            "-ss",
+            overlap.seek,
+            "-t",
+            overlap.duration,
+            "-i",
+            overlap.source.clone()
+        ],
+        None => vec![],
+    }
+}
+
+/// Input for live stream, with timeout for connecting and reading.
+pub fn live_input(node: &Media, timeout: f64) -> Vec<String> {
+    vec_strings![
+        "-rw_timeout",
+        (timeout * 1_000_000.0) as i64,
+        "-i",
+        node.source.clone(),
+        "-t",
+        node.out - node.seek
+    ]
+}
+
 /// Loop filler until target duration is reached.
 pub fn loop_filler(node: &Media) -> Vec<String> {
     let loop_count = (node.out / node.duration).ceil() as i32;
@@ -520,6 +905,8 @@ pub fn seek_and_length(node: &Media) -> Vec<String> {
         }
     }
 
+    source_cmd.append(&mut overlap_input(node));
+
     if node.duration > node.out || cut_audio {
         source_cmd.append(&mut vec_strings!["-t", node.out - node.seek]);
     }
@@ -631,10 +1018,13 @@ pub fn include_file(config: PlayoutConfig, file_path: &Path) -> bool {
 }
 
 /// Read ffmpeg stderr decoder and encoder instance
-/// and log the output.
+/// and log the output. Fatal errors are reported to the supervisor from [ProcessControl].
+///
+/// Errors from a live stream are only logged, a broken stream gets replaced by filler.
 pub fn stderr_reader(
     buffer: BufReader<ChildStderr>,
     suffix: ProcessUnit,
+    is_live: bool,
     mut proc_control: ProcessControl,
 ) -> Result<(), Error> {
     for line in buffer.lines() {
@@ -660,14 +1050,20 @@ pub fn stderr_reader(
                 line.replace("[error] ", "").replace("[fatal] ", "")
             );
 
-            if line.contains("Invalid argument")
-                || line.contains("Numerical result")
-                || (line.contains("No such file or directory")
-                    && !line.contains("failed to delete old segment"))
-                || line.contains("Error initializing complex filters")
+            if !is_live
+                && (line.contains("Invalid argument")
+                    || line.contains("Numerical result")
+                    || (line.contains("No such file or directory")
+                        && !line.contains("failed to delete old segment"))
+                    || line.contains("Error initializing complex filters"))
             {
-                proc_control.kill_all();
-                exit(1);
+                let reason = line.replace("[error] ", "").replace("[fatal] ", "");
+
+                // process gets restarted from the player, until there are too many failures
+                if !proc_control.failed(suffix, &reason) {
+                    proc_control.kill_all();
+                    exit(1);
+                }
             }
         }
     }
@@ -889,13 +1285,15 @@ fn home_dir_inner() -> Option<PathBuf> {
     env::home_dir()
 }
 
-/// Get system time, in non test/debug case.
+/// Get time from playout clock, in non test/debug case.
 #[cfg(not(any(test, debug_assertions)))]
 pub fn time_now() -> DateTime<Local> {
-    Local::now()
+    (clock().now() + look_ahead()).with_timezone(&Local)
 }
 
 /// Get mocked system time, in test/debug case.
+///
+/// Without mocked time, the playout clock is used.
 #[cfg(any(test, debug_assertions))]
 pub mod mock_time {
     use super::*;
@@ -907,13 +1305,18 @@ pub mod mock_time {
 
     pub fn time_now() -> DateTime<Local> {
         DATE_TIME_DIFF.with(|cell| match cell.borrow().as_ref().cloned() {
-            Some(diff) => Local::now() - diff,
-            None => Local::now(),
+            Some(diff) => Local::now() - diff + look_ahead(),
+            None => (clock().now() + look_ahead()).with_timezone(&Local),
         })
     }
 
+    /// Set mocked time, as local time or with offset (RFC 3339).
     pub fn set_mock_time(date_time: &str) {
-        if let Ok(d) = NaiveDateTime::parse_from_str(date_time, "%Y-%m-%dT%H:%M:%S") {
+        if let Ok(time) = DateTime::parse_from_rfc3339(date_time) {
+            let time = time.with_timezone(&Local);
+
+            DATE_TIME_DIFF.with(|cell| *cell.borrow_mut() = Some(Local::now() - time));
+        } else if let Ok(d) = NaiveDateTime::parse_from_str(date_time, "%Y-%m-%dT%H:%M:%S") {
             let time = Local.from_local_datetime(&d).unwrap();
 
             DATE_TIME_DIFF.with(|cell| *cell.borrow_mut() = Some(Local::now() - time));
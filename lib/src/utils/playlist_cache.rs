@@ -0,0 +1,201 @@
+/// Remote Playlist Cache
+///
+/// Remote playlists are stored locally, together with their ETag and Last-Modified header.
+/// Requests are send conditional, so unchanged playlists are not downloaded again.
+/// When the server is not reachable, or responds with an invalid playlist,
+/// the last known good copy from cache is used. The marker from an invalid playlist
+/// is remembered, so the same version is not downloaded again and again.
+use std::{
+    env, fs,
+    io::Error,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local};
+use reqwest::{blocking::Client, header, StatusCode};
+use serde::{Deserialize, Serialize};
+use simplelog::*;
+
+use crate::utils::{json_serializer::JsonPlaylist, modified_from_header, PlayoutConfig};
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct CacheMeta {
+    #[serde(default)]
+    etag: String,
+    #[serde(default)]
+    last_modified: String,
+}
+
+impl CacheMeta {
+    /// Modification marker in the same form like `modified_from_header`.
+    fn modified(&self) -> Option<String> {
+        if let Ok(time) = DateTime::parse_from_rfc2822(&self.last_modified) {
+            return Some(DateTime::<Local>::from(time).to_string());
+        }
+
+        if !self.etag.is_empty() {
+            return Some(self.etag.clone());
+        }
+
+        None
+    }
+}
+
+fn cache_file(config: &PlayoutConfig, url: &str) -> PathBuf {
+    let cache_dir = match config.playlist.cache_path.is_empty() {
+        true => env::temp_dir().join("ffplayout"),
+        false => PathBuf::from(&config.playlist.cache_path),
+    };
+    let name: String = url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    cache_dir.join(name)
+}
+
+fn meta_file(cache: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.meta", cache.display()))
+}
+
+fn write_cache(cache: &Path, body: &str, meta: &CacheMeta) -> Result<(), Error> {
+    if let Some(dir) = cache.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(cache, body)?;
+    fs::write(meta_file(cache), serde_json::to_string(meta)?)?;
+
+    Ok(())
+}
+
+fn header_value(headers: &header::HeaderMap, name: header::HeaderName) -> String {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Get remote playlist, with its modification marker.
+///
+/// Returns None only, when the playlist is not available from server and not from cache.
+pub fn fetch_remote_playlist(
+    config: &PlayoutConfig,
+    url: &str,
+) -> Option<(JsonPlaylist, Option<String>)> {
+    let cache = cache_file(config, url);
+    let meta: CacheMeta = fs::read_to_string(meta_file(&cache))
+        .ok()
+        .and_then(|m| serde_json::from_str(&m).ok())
+        .unwrap_or_default();
+    let mut request = Client::new().get(url).timeout(Duration::from_secs(10));
+    let mut not_modified = false;
+    let mut rejected = None;
+
+    if cache.is_file() {
+        if !meta.etag.is_empty() {
+            request = request.header(header::IF_NONE_MATCH, &meta.etag);
+        }
+
+        if !meta.last_modified.is_empty() {
+            request = request.header(header::IF_MODIFIED_SINCE, &meta.last_modified);
+        }
+    }
+
+    match request.send() {
+        Ok(resp) if resp.status() == StatusCode::NOT_MODIFIED => {
+            debug!("Remote playlist <b><magenta>{url}</></b> not modified, read from cache");
+            not_modified = true;
+        }
+        Ok(resp) if resp.status().is_success() => {
+            let headers = resp.headers().clone();
+            let body = resp.text().unwrap_or_default();
+            let new_meta = CacheMeta {
+                etag: header_value(&headers, header::ETAG),
+                last_modified: header_value(&headers, header::LAST_MODIFIED),
+            };
+
+            match serde_json::from_str::<JsonPlaylist>(&body) {
+                Ok(playlist) => {
+                    if let Err(e) = write_cache(&cache, &body, &new_meta) {
+                        warn!(
+                            "Can't write playlist cache <b><magenta>{}</></b>: {e}",
+                            cache.display()
+                        );
+                    }
+
+                    return Some((playlist, modified_from_header(&headers)));
+                }
+                Err(e) => {
+                    error!("Remote playlist <b><magenta>{url}</></b> is invalid: {e}");
+                    rejected = Some(new_meta);
+                }
+            }
+        }
+        Ok(resp) => error!(
+            "Remote playlist <b><magenta>{url}</></b> responds with: {}",
+            resp.status()
+        ),
+        Err(e) => error!("Remote playlist <b><magenta>{url}</></b> not reachable: {e}"),
+    }
+
+    let playlist = fs::read_to_string(&cache)
+        .ok()
+        .and_then(|body| serde_json::from_str::<JsonPlaylist>(&body).ok())?;
+
+    if !not_modified {
+        warn!(
+            "Use last known good playlist from cache: <b><magenta>{}</></b>",
+            cache.display()
+        );
+    }
+
+    // remember the marker from the invalid version, so it is not downloaded again,
+    // and the playout sees no change until the server has a new version
+    if let Some(rejected) = rejected {
+        if let Err(e) = serde_json::to_string(&rejected)
+            .map_err(Error::from)
+            .and_then(|m| fs::write(meta_file(&cache), m))
+        {
+            warn!(
+                "Can't write playlist cache <b><magenta>{}</></b>: {e}",
+                cache.display()
+            );
+        }
+
+        return Some((playlist, rejected.modified()));
+    }
+
+    Some((playlist, meta.modified()))
+}
+
+/// Load playlist from given date into cache, before it is needed.
+pub fn prefetch_remote_playlist(config: &PlayoutConfig, date: &str) {
+    let d: Vec<&str> = date.split('-').collect();
+
+    if d.len() != 3 {
+        return;
+    }
+
+    let url = Path::new(&config.playlist.path)
+        .join(d[0])
+        .join(d[1])
+        .join(date)
+        .with_extension("json")
+        .display()
+        .to_string();
+
+    if fetch_remote_playlist(config, &url).is_some() {
+        info!("Prefetch playlist: <b><magenta>{url}</></b>");
+    }
+}
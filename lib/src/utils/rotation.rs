@@ -0,0 +1,202 @@
+/// Rotation Rules
+///
+/// Clock wheel for folder mode and the playlist generator. Rules are set in the storage config:
+///
+/// - `every`: after every N clips, one clip from the folder is played, like jingles.
+/// - `hourly`: when a new hour has begun, one clip from the folder is played, like idents.
+///   Clips are not cut for it, so it plays after the clip which runs over the full hour.
+/// - `percent`: the clips from the folder get this share from all regular clips.
+///
+/// Clips from `every` and `hourly` folders are not played as regular clips.
+/// Regular clips, which are in no rule folder, get the percent which is left.
+use std::path::{Path, PathBuf};
+
+use rand::{seq::SliceRandom, thread_rng};
+
+use crate::utils::{Media, PlayoutConfig};
+
+#[derive(Debug, Clone)]
+struct Pool {
+    path: Option<PathBuf>,
+    every: usize,
+    hourly: bool,
+    percent: f64,
+    clips: Vec<Media>,
+    cursor: usize,
+    played: usize,
+}
+
+impl Pool {
+    fn is_regular(&self) -> bool {
+        self.every == 0 && !self.hourly
+    }
+
+    fn contains(&self, source: &str) -> bool {
+        self.path
+            .as_ref()
+            .map_or(true, |p| Path::new(source).starts_with(p))
+    }
+
+    /// Next clip in the pool, clips which are deleted in meantime are skipped.
+    fn next(&mut self, shuffle: bool) -> Option<Media> {
+        for _ in 0..self.clips.len() {
+            if self.cursor == 0 && shuffle {
+                self.clips.shuffle(&mut thread_rng());
+            }
+
+            let clip = self.clips[self.cursor].clone();
+            self.cursor = (self.cursor + 1) % self.clips.len();
+
+            if Path::new(&clip.source).is_file() {
+                return Some(clip);
+            }
+        }
+
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Rotation {
+    shuffle: bool,
+    pools: Vec<Pool>,
+    count: usize,
+    hour: Option<i64>,
+}
+
+impl Rotation {
+    /// Sort clips in the pools from the rules, None when there are no rules.
+    pub fn new(config: &PlayoutConfig, clips: &[Media]) -> Option<Self> {
+        if config.storage.rotation.is_empty() {
+            return None;
+        }
+
+        let mut pools: Vec<Pool> = config
+            .storage
+            .rotation
+            .iter()
+            .map(|rule| Pool {
+                path: Some(Path::new(&config.storage.path).join(&rule.folder)),
+                every: rule.every,
+                hourly: rule.hourly,
+                percent: rule.percent.max(0.0),
+                clips: vec![],
+                cursor: 0,
+                played: 0,
+            })
+            .collect();
+
+        let weighted: f64 = pools
+            .iter()
+            .filter(|p| p.is_regular())
+            .map(|p| p.percent)
+            .sum();
+
+        // all other clips
+        pools.push(Pool {
+            path: None,
+            every: 0,
+            hourly: false,
+            percent: (100.0 - weighted).max(0.0),
+            clips: vec![],
+            cursor: 0,
+            played: 0,
+        });
+
+        let mut rotation = Self {
+            shuffle: config.storage.shuffle,
+            pools,
+            count: 0,
+            hour: None,
+        };
+
+        rotation.update(clips);
+
+        Some(rotation)
+    }
+
+    /// Add new clips to the pool from their folder.
+    pub fn update(&mut self, clips: &[Media]) {
+        for clip in clips {
+            if self
+                .pools
+                .iter()
+                .any(|p| p.clips.iter().any(|c| c.source == clip.source))
+            {
+                continue;
+            }
+
+            if let Some(pool) = self.pools.iter_mut().find(|p| p.contains(&clip.source)) {
+                let mut clip = clip.clone();
+                clip.index = None;
+
+                pool.clips.push(clip);
+
+                if !self.shuffle {
+                    pool.clips.sort_by(|a, b| a.source.cmp(&b.source));
+                }
+            }
+        }
+    }
+
+    /// Build the next round, every regular clip gets one place in it.
+    ///
+    /// Regular clips are taken from the pool, which is most behind its percent,
+    /// after every N of them the clips from `every` rules are inserted.
+    pub fn round(&mut self) -> Vec<Media> {
+        let length: usize = self
+            .pools
+            .iter()
+            .filter(|p| p.is_regular())
+            .map(|p| p.clips.len())
+            .sum();
+        let mut list = vec![];
+
+        for _ in 0..length {
+            let total = self.pools.iter().map(|p| p.played).sum::<usize>() as f64 + 1.0;
+            let next = self
+                .pools
+                .iter_mut()
+                .filter(|p| p.is_regular() && !p.clips.is_empty())
+                .max_by(|a, b| {
+                    let deficit = |p: &Pool| p.percent / 100.0 * total - p.played as f64;
+                    deficit(a).total_cmp(&deficit(b))
+                })
+                .and_then(|pool| {
+                    pool.played += 1;
+                    pool.next(self.shuffle)
+                });
+
+            if let Some(clip) = next {
+                list.push(clip);
+                self.count += 1;
+
+                for pool in self.pools.iter_mut() {
+                    if pool.every > 0 && self.count % pool.every == 0 {
+                        if let Some(clip) = pool.next(self.shuffle) {
+                            list.push(clip);
+                        }
+                    }
+                }
+            }
+        }
+
+        list
+    }
+
+    /// Clip for the new hour, when the clip at `time` begins in another hour than the last one.
+    pub fn hourly(&mut self, time: f64) -> Option<Media> {
+        let hour = (time.rem_euclid(86400.0) / 3600.0).floor() as i64;
+
+        if self.hour.replace(hour).map_or(true, |h| h == hour) {
+            return None;
+        }
+
+        let shuffle = self.shuffle;
+
+        self.pools
+            .iter_mut()
+            .filter(|p| p.hourly)
+            .find_map(|p| p.next(shuffle))
+    }
+}
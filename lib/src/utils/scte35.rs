@@ -0,0 +1,142 @@
+/// SCTE-35 Splice Insert
+///
+/// Build splice_insert sections, which signal the begin (cue out) and the end (cue in)
+/// of an ad break. The splice happens immediately, so no PTS from the encoder is needed.
+///
+/// A section can be packed in a MPEG-TS packet, or written as hex string to HLS DATERANGE tags.
+const TABLE_ID: u8 = 0xFC;
+const SPLICE_INSERT: u8 = 0x05;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpliceInsert {
+    pub event_id: u32,
+    pub out_of_network: bool,
+    pub duration: Option<f64>,
+}
+
+impl SpliceInsert {
+    /// Begin of ad break, with its planned length in seconds.
+    pub fn cue_out(event_id: u32, duration: f64) -> Self {
+        Self {
+            event_id,
+            out_of_network: true,
+            duration: Some(duration),
+        }
+    }
+
+    /// End of ad break, back to network.
+    pub fn cue_in(event_id: u32) -> Self {
+        Self {
+            event_id,
+            out_of_network: false,
+            duration: None,
+        }
+    }
+
+    fn command(&self) -> Vec<u8> {
+        let mut cmd = self.event_id.to_be_bytes().to_vec();
+
+        // splice_event_cancel_indicator is 0, followed by reserved bits
+        cmd.push(0x7F);
+        // program_splice_flag and splice_immediate_flag are always set
+        cmd.push(
+            ((self.out_of_network as u8) << 7)
+                | (1 << 6)
+                | ((self.duration.is_some() as u8) << 5)
+                | (1 << 4)
+                | 0x0F,
+        );
+
+        if let Some(duration) = self.duration {
+            // break_duration with auto_return, in 90 kHz ticks
+            let ticks = (duration.max(0.0) * 90000.0).round() as u64 & 0x1_FFFF_FFFF;
+
+            cmd.push(0x80 | 0x7E | (ticks >> 32) as u8);
+            cmd.extend_from_slice(&(ticks as u32).to_be_bytes());
+        }
+
+        // unique_program_id, avail_num, avails_expected
+        cmd.extend_from_slice(&[0x00, 0x01, 0x00, 0x00]);
+
+        cmd
+    }
+
+    /// Complete splice_info_section, with CRC.
+    pub fn section(&self) -> Vec<u8> {
+        let cmd = self.command();
+        // everything after section_length: header, command type, command, descriptors and CRC
+        let section_length = 10 + 1 + cmd.len() + 2 + 4;
+        let mut section = vec![
+            TABLE_ID,
+            // section_syntax_indicator 0, private_indicator 0, sap_type 3
+            0x30 | (section_length >> 8) as u8,
+            section_length as u8,
+            // protocol_version
+            0x00,
+            // encrypted_packet, encryption_algorithm and pts_adjustment
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            // cw_index
+            0x00,
+            // tier 0xFFF and splice_command_length
+            0xFF,
+            0xF0 | (cmd.len() >> 8) as u8,
+            cmd.len() as u8,
+            SPLICE_INSERT,
+        ];
+
+        section.extend_from_slice(&cmd);
+        // descriptor_loop_length
+        section.extend_from_slice(&[0x00, 0x00]);
+
+        let crc = crc32_mpeg2(&section);
+        section.extend_from_slice(&crc.to_be_bytes());
+
+        section
+    }
+
+    /// Section as hex string, like it is used in HLS DATERANGE tags.
+    pub fn hex(&self) -> String {
+        let hex: String = self.section().iter().map(|b| format!("{b:02X}")).collect();
+
+        format!("0x{hex}")
+    }
+
+    /// Section in a single MPEG-TS packet, on the given PID.
+    pub fn ts_packet(&self, pid: u16, continuity_counter: u8) -> [u8; 188] {
+        let mut packet = [0xFF; 188];
+        let section = self.section();
+
+        packet[0] = 0x47;
+        // payload_unit_start_indicator is set, section begins in this packet
+        packet[1] = 0x40 | ((pid >> 8) as u8 & 0x1F);
+        packet[2] = pid as u8;
+        packet[3] = 0x10 | (continuity_counter & 0x0F);
+        // pointer_field
+        packet[4] = 0x00;
+        packet[5..5 + section.len()].copy_from_slice(&section);
+
+        packet
+    }
+}
+
+/// CRC-32 from MPEG-2 systems, which is used in PSI and SCTE-35 sections.
+pub fn crc32_mpeg2(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF_u32;
+
+    for byte in data {
+        crc ^= (*byte as u32) << 24;
+
+        for _ in 0..8 {
+            crc = match crc & 0x8000_0000 {
+                0 => crc << 1,
+                _ => (crc << 1) ^ 0x04C1_1DB7,
+            };
+        }
+    }
+
+    crc
+}
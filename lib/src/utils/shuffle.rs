@@ -0,0 +1,215 @@
+/// Shuffle Strategy
+///
+/// Folder mode shuffles its files for every pass. Clips, which have run in the last
+/// `no_repeat_count` clips, or in the last `no_repeat_time` seconds, are moved back in the
+/// new pass, so they not run twice in a row across the pass boundary.
+///
+/// With `weights` or `weight_by_age`, clips with a higher weight come earlier in a pass.
+/// The recent history is saved beside the status file, so it survives a restart.
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use simplelog::*;
+
+use crate::utils::{state_file, Media, PlayoutConfig};
+
+/// Upper limit for the history, when the time window is very long.
+const MAX_HISTORY: usize = 10_000;
+
+/// Age in days, after which the extra weight from a new file is halved.
+const AGE_HALF_LIFE: f64 = 7.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub source: String,
+    pub duration: f64,
+}
+
+/// Recently played clips, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct PlayHistory {
+    path: Option<PathBuf>,
+    count: usize,
+    time: f64,
+    pub entries: VecDeque<HistoryEntry>,
+}
+
+impl PlayHistory {
+    /// Load history from last run, it is only saved in playout, not when generating playlists.
+    pub fn new(config: &PlayoutConfig) -> Self {
+        let path = state_file(config, "history");
+        let entries = path
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        Self {
+            path,
+            count: config.storage.no_repeat_count,
+            time: config.storage.no_repeat_time,
+            entries,
+        }
+    }
+
+    /// Remember played clip, entries outside from the no-repeat window are dropped.
+    pub fn add(&mut self, node: &Media) {
+        self.entries.push_back(HistoryEntry {
+            source: node.source.clone(),
+            duration: node.out - node.seek,
+        });
+
+        let mut airtime: f64 = self.entries.iter().map(|e| e.duration).sum();
+
+        while let Some(first) = self.entries.front() {
+            airtime -= first.duration;
+
+            if self.entries.len() <= MAX_HISTORY
+                && (self.entries.len() <= self.count || airtime < self.time)
+            {
+                break;
+            }
+
+            self.entries.pop_front();
+        }
+
+        self.save();
+    }
+
+    fn save(&self) {
+        if let Some(path) = &self.path {
+            let json = serde_json::to_string(&self.entries).unwrap();
+
+            if let Err(e) = fs::write(path, json) {
+                error!("Unable to write history file <b><magenta>{path:?}</></b>: {e}");
+            }
+        }
+    }
+
+    /// Clips and seconds, which have run since each clip from the history.
+    fn since(&self) -> HashMap<&str, (usize, f64)> {
+        let mut since = HashMap::new();
+        let mut airtime = 0.0;
+
+        for (count, entry) in self.entries.iter().rev().enumerate() {
+            since
+                .entry(entry.source.as_str())
+                .or_insert((count, airtime));
+            airtime += entry.duration;
+        }
+
+        since
+    }
+}
+
+/// Weight from config for a clip, the longest matching path in `weights` counts.
+fn weight(config: &PlayoutConfig, clip: &Media) -> f64 {
+    let source = Path::new(&clip.source);
+    let storage = Path::new(&config.storage.path);
+    let mut weight = config
+        .storage
+        .weights
+        .iter()
+        .filter(|(path, _)| source.starts_with(storage.join(path)))
+        .max_by_key(|(path, _)| path.len())
+        .map_or(1.0, |(_, w)| *w);
+
+    if config.storage.weight_by_age {
+        let age = fs::metadata(source)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| SystemTime::now().duration_since(t).ok())
+            .map_or(f64::INFINITY, |d| d.as_secs_f64() / 86400.0);
+
+        weight *= 1.0 + 0.5_f64.powf(age / AGE_HALF_LIFE);
+    }
+
+    weight
+}
+
+/// Shuffle clips for a new pass, by weight and with the no-repeat window from config.
+pub fn shuffle_clips(config: &PlayoutConfig, clips: &mut Vec<Media>, history: &PlayHistory) {
+    let mut rng = thread_rng();
+
+    // weighted random order, with key u^(1/w) (Efraimidis-Spirakis)
+    let mut keyed: Vec<(f64, Media)> = clips
+        .drain(..)
+        .map(|clip| {
+            let w = weight(config, &clip);
+            let key = match w > 0.0 {
+                true => rng.gen::<f64>().powf(1.0 / w),
+                false => 0.0,
+            };
+
+            (key, clip)
+        })
+        .collect();
+
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    *clips = no_repeat_order(
+        keyed.into_iter().map(|(_, c)| c).collect(),
+        history,
+        config.storage.no_repeat_count,
+        config.storage.no_repeat_time,
+    );
+}
+
+/// Move clips back, which would run again within `count` clips or `time` seconds.
+///
+/// The order from the other clips stays. When no clip is out of the window,
+/// the one which has run longest ago comes next.
+pub fn no_repeat_order(
+    clips: Vec<Media>,
+    history: &PlayHistory,
+    count: usize,
+    time: f64,
+) -> Vec<Media> {
+    let since = history.since();
+    // clips in folder mode are not probed yet
+    let average = match history.entries.is_empty() {
+        true => 0.0,
+        false => {
+            history.entries.iter().map(|e| e.duration).sum::<f64>() / history.entries.len() as f64
+        }
+    };
+    let mut rest: VecDeque<Media> = clips.into();
+    let mut list = Vec::with_capacity(rest.len());
+    let mut airtime = 0.0;
+
+    while !rest.is_empty() {
+        let placed = list.len();
+        let gap = |clip: &Media| {
+            since
+                .get(clip.source.as_str())
+                .map(|(c, t)| (c + placed, t + airtime))
+        };
+
+        let position = rest
+            .iter()
+            .position(|clip| gap(clip).map_or(true, |(c, t)| c >= count && t >= time))
+            .unwrap_or_else(|| {
+                rest.iter()
+                    .enumerate()
+                    .max_by_key(|(_, clip)| gap(clip).map_or(usize::MAX, |(c, _)| c))
+                    .map_or(0, |(i, _)| i)
+            });
+
+        let clip = rest.remove(position).unwrap();
+
+        airtime += match clip.duration > 0.0 {
+            true => clip.duration,
+            false => average,
+        };
+
+        list.push(clip);
+    }
+
+    list
+}
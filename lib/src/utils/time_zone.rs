@@ -0,0 +1,108 @@
+/// Channel Time Zone
+///
+/// The playout counts the seconds since local midnight of the channel time zone.
+/// This are real seconds, so on days with a DST change the wall clock and the
+/// counted time differ, and the day is 23 or 25 hours long.
+///
+/// When no time zone is set, the local time zone from the system is used.
+use std::sync::Mutex;
+
+use chrono::{prelude::*, Duration};
+use chrono_tz::Tz;
+
+static TIME_ZONE: Mutex<Option<Tz>> = Mutex::new(None);
+
+/// Parse IANA time zone name, like `Europe/Berlin`. Empty name means system time zone.
+pub fn parse_time_zone(name: &str) -> Result<Option<Tz>, String> {
+    if name.trim().is_empty() {
+        return Ok(None);
+    }
+
+    name.trim()
+        .parse::<Tz>()
+        .map(Some)
+        .map_err(|e| format!("Time zone <b><magenta>{name}</></b> is not valid: {e}"))
+}
+
+/// Set time zone for the running channel.
+pub fn set_time_zone(name: &str) -> Result<(), String> {
+    let tz = parse_time_zone(name)?;
+    *TIME_ZONE.lock().unwrap() = tz;
+
+    Ok(())
+}
+
+pub fn time_zone() -> Option<Tz> {
+    *TIME_ZONE.lock().unwrap()
+}
+
+fn local_to_instant<T: TimeZone>(tz: &T, naive: NaiveDateTime) -> DateTime<T> {
+    match tz.from_local_datetime(&naive).earliest() {
+        Some(time) => time,
+        // wall time is in a DST gap, one hour later has the same distance to midnight
+        None => tz
+            .from_local_datetime(&(naive + Duration::hours(1)))
+            .earliest()
+            .unwrap_or_else(|| tz.from_utc_datetime(&naive)),
+    }
+}
+
+/// Convert wall time from channel time zone to UTC.
+pub fn channel_instant(tz: Option<Tz>, naive: NaiveDateTime) -> DateTime<Utc> {
+    match tz {
+        Some(tz) => local_to_instant(&tz, naive).with_timezone(&Utc),
+        None => local_to_instant(&Local, naive).with_timezone(&Utc),
+    }
+}
+
+/// Convert UTC time to channel time zone, with the offset which is valid at this time.
+pub fn channel_time(tz: Option<Tz>, time: DateTime<Utc>) -> DateTime<FixedOffset> {
+    match tz {
+        Some(tz) => {
+            let time = time.with_timezone(&tz);
+            time.with_timezone(&time.offset().fix())
+        }
+        None => {
+            let time = time.with_timezone(&Local);
+            time.with_timezone(&time.offset().fix())
+        }
+    }
+}
+
+fn midnight(date: NaiveDate) -> NaiveDateTime {
+    date.and_hms_opt(0, 0, 0).unwrap()
+}
+
+/// Real seconds from midnight to the given wall time (can be over 24 hours) at date.
+pub fn real_sec(tz: Option<Tz>, date: NaiveDate, wall: f64) -> f64 {
+    let start = channel_instant(tz, midnight(date));
+    let time = channel_instant(
+        tz,
+        midnight(date) + Duration::milliseconds((wall * 1000.0).round() as i64),
+    );
+
+    (time - start).num_milliseconds() as f64 / 1000.0
+}
+
+/// Real length from the calendar day, 82800 or 90000 seconds on days with DST change.
+pub fn day_length(tz: Option<Tz>, date: NaiveDate) -> f64 {
+    real_sec(tz, date, 86400.0)
+}
+
+/// Start and length in real seconds, from the broadcast day which begins at date.
+pub fn broadcast_day(tz: Option<Tz>, date: NaiveDate, day_start: f64) -> (f64, f64) {
+    let start = real_sec(tz, date, day_start);
+    let end = real_sec(tz, date, day_start + 86400.0);
+
+    (start, end - start)
+}
+
+/// Real seconds since local midnight.
+pub fn sec_since_midnight(tz: Option<Tz>, time: DateTime<Utc>) -> f64 {
+    let date = channel_time(tz, time).naive_local().date();
+
+    (time - channel_instant(tz, midnight(date)))
+        .num_microseconds()
+        .unwrap_or_default() as f64
+        / 1_000_000.0
+}
@@ -2,7 +2,7 @@ use std::fs;
 
 use ffplayout::{input::playlist::gen_source, utils::prepare_output_cmd};
 use ffplayout_lib::{
-    utils::{Media, OutputMode::*, PlayoutConfig, ProcessUnit::*},
+    utils::{Media, OutputMode::*, Overlap, PlayoutConfig, ProcessUnit::*},
     vec_strings,
 };
 
@@ -111,6 +111,71 @@ fn dual_audio_aevalsrc_input() {
     assert_eq!(media.filter.unwrap().map(), test_filter_map);
 }
 
+#[test]
+fn video_audio_transition_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+
+    let mut media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    media_obj.overlap = Some(Overlap {
+        kind: "dissolve".to_string(),
+        source: "./assets/with_audio.mp4".to_string(),
+        seek: 5.0,
+        duration: 1.0,
+        audio_streams: Some(1),
+    });
+    let media = gen_source(&config, media_obj, &None);
+
+    let test_filter_cmd =
+        vec_strings![
+            "-filter_complex",
+            "[0:v:0]scale=1024:576,fps=25,format=yuv420p,setsar=1[vmain0];[1:v:0]scale=1024:576:force_original_aspect_ratio=decrease,pad=1024:576:(ow-iw)/2:(oh-ih)/2,fps=25,format=yuv420p,setsar=1[vtail0];[vtail0][vmain0]xfade=transition=dissolve:duration=1:offset=0[vout0];[0:a:0]anull[amain0];[1:a:0]anull[atail0];[atail0][amain0]acrossfade=d=1[aout0]"
+        ];
+
+    assert_eq!(
+        media.cmd,
+        Some(vec_strings![
+            "-i",
+            "./assets/with_audio.mp4",
+            "-ss",
+            "5",
+            "-t",
+            "1",
+            "-i",
+            "./assets/with_audio.mp4"
+        ])
+    );
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
+}
+
+#[test]
+fn transition_streams_from_last() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+
+    let last = Media::new(0, "./assets/with_audio.mp4", true);
+    let mut media_obj = Media::new(1, "./assets/with_audio.mp4", true);
+    media_obj.overlap = Some(Overlap {
+        kind: "fade".to_string(),
+        source: "./assets/with_audio.mp4".to_string(),
+        seek: 5.0,
+        duration: 1.0,
+        audio_streams: None,
+    });
+
+    // without probe from the previous clip, the transition is skipped
+    let media = gen_source(&config, media_obj.clone(), &None);
+    assert!(media.overlap.is_none());
+
+    media_obj.carry_overlap(&last);
+    assert_eq!(media_obj.overlap.as_ref().unwrap().audio_streams, Some(1));
+
+    let media = gen_source(&config, media_obj, &None);
+    assert!(media.overlap.is_some());
+}
+
 #[test]
 fn dual_audio_input() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
@@ -169,6 +234,30 @@ fn video_separate_audio_input() {
     assert_eq!(media.filter.unwrap().map(), test_filter_map);
 }
 
+#[test]
+fn video_audio_text_template_input() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.text.add_text = true;
+    config.text.text_from_filename = true;
+    config.text.fontfile = String::new();
+    config.text.template = "{title} - S{season}E{episode}".to_string();
+
+    let mut media_obj = Media::new(0, "./assets/with_audio.mp4", true);
+    media_obj.title = "News".to_string();
+    media_obj.season = Some(2);
+    media_obj.episode = Some(3);
+    let media = gen_source(&config, media_obj, &None);
+
+    let filter_cmd = media.filter.unwrap().cmd();
+
+    assert!(filter_cmd[1].contains(&format!(
+        "drawtext=text='News - S2E3':{}",
+        config.text.style
+    )));
+}
+
 #[test]
 fn video_audio_stream() {
     let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
@@ -1,12 +1,22 @@
 use std::{
+    env,
+    process::Command,
+    sync::atomic::{AtomicBool, Ordering},
     thread::{self, sleep},
     time::Duration,
 };
 
 use simplelog::*;
 
-use ffplayout::output::player;
-use ffplayout_lib::utils::*;
+use ffplayout::{
+    input::{
+        playlist::{merge_program, recover_sync},
+        source_generator,
+    },
+    output::{list_source, player, pre_roll_valid, slate_node},
+    rpc::{clip_at, index_time, move_to, playlist_time, position_time},
+};
+use ffplayout_lib::{utils::*, vec_strings};
 
 fn timed_kill(sec: u64, mut proc_ctl: ProcessControl) {
     sleep(Duration::from_secs(sec));
@@ -63,3 +73,307 @@ fn playlist_change_at_six() {
 
     player(&config, play_control, playout_stat, proc_control);
 }
+
+fn media(source: &str, begin: f64, out: f64) -> Media {
+    let mut media = Media::new(0, source, false);
+    media.begin = Some(begin);
+    media.out = out;
+    media.duration = out;
+
+    media
+}
+
+#[test]
+fn playlist_merge_after_current() {
+    let current = vec![
+        media("a.mp4", 0.0, 10.0),
+        media("b.mp4", 10.0, 10.0),
+        media("c.mp4", 20.0, 10.0),
+        media("d.mp4", 30.0, 10.0),
+    ];
+    let new = vec![
+        media("a.mp4", 0.0, 10.0),
+        media("b.mp4", 10.0, 10.0),
+        media("c.mp4", 20.0, 15.0),
+        media("d.mp4", 35.0, 10.0),
+        media("e.mp4", 45.0, 10.0),
+    ];
+
+    let (program, changes) = merge_program(&current, new, 2);
+    let sources: Vec<&str> = program.iter().map(|m| m.source.as_str()).collect();
+
+    assert_eq!(sources, vec!["a.mp4", "b.mp4", "c.mp4", "d.mp4", "e.mp4"]);
+    assert_eq!(program[2].out, 15.0);
+    assert_eq!(program[4].index, Some(4));
+    assert_eq!((changes.changed, changes.added, changes.removed), (1, 1, 0));
+}
+
+#[test]
+fn insert_clip_before_source() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.general.stat_file = String::new();
+    config.processing.mode = Folder;
+    config.storage.path = "assets".into();
+    config.storage.shuffle = false;
+
+    let play_control = PlayerControl::new();
+    let playout_stat = PlayoutStatus::new();
+    let proc_control = ProcessControl::new();
+
+    let mut insert = Media::new(0, "assets/with_audio.mp4", true);
+    insert.out = insert.duration;
+    *playout_stat.insert.lock().unwrap() = Some(insert);
+
+    let mut source = source_generator(
+        config,
+        play_control.current_list.clone(),
+        play_control.index.clone(),
+        playout_stat.clone(),
+        proc_control.is_terminated.clone(),
+    );
+
+    let first = source.next().unwrap();
+    let second = source.next().unwrap();
+
+    assert_eq!(first.source, "assets/with_audio.mp4");
+    assert!(first.cmd.is_some());
+    assert_ne!(second.source, "assets/with_audio.mp4");
+    assert!(playout_stat.insert.lock().unwrap().is_none());
+
+    proc_control.is_terminated.store(true, Ordering::SeqCst);
+}
+
+#[test]
+fn pre_roll_invalidation() {
+    let play_control = PlayerControl::new();
+    let playlist_init = AtomicBool::new(false);
+
+    *play_control.current_list.lock().unwrap() = vec![
+        media("a.mp4", 0.0, 10.0),
+        media("b.mp4", 10.0, 10.0),
+        media("c.mp4", 20.0, 10.0),
+    ];
+    play_control.index.store(1, Ordering::SeqCst);
+
+    // decoder for list item 1 gets pre-rolled, while item 0 plays
+    let source = list_source(&play_control, 1);
+
+    assert!(pre_roll_valid(&play_control, &playlist_init, 1, &source));
+
+    // jump to another position
+    play_control.index.store(2, Ordering::SeqCst);
+    assert!(!pre_roll_valid(&play_control, &playlist_init, 1, &source));
+    play_control.index.store(1, Ordering::SeqCst);
+
+    // playlist reload has changed the next item
+    play_control.current_list.lock().unwrap()[1] = media("x.mp4", 10.0, 10.0);
+    assert!(!pre_roll_valid(&play_control, &playlist_init, 1, &source));
+    play_control.current_list.lock().unwrap()[1] = media("b.mp4", 10.0, 10.0);
+    assert!(pre_roll_valid(&play_control, &playlist_init, 1, &source));
+
+    // playlist gets initialized again
+    playlist_init.store(true, Ordering::SeqCst);
+    assert!(!pre_roll_valid(&play_control, &playlist_init, 1, &source));
+}
+
+#[test]
+fn rpc_playlist_time() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.playlist.timezone = "UTC".into();
+    config.playlist.day_start = "06:00:00".into();
+    config.playlist.start_sec = Some(21600.0);
+
+    assert_eq!(
+        playlist_time(&config, "2022-05-10", "12:30:00"),
+        Some(45000.0)
+    );
+
+    // before day start, the time belongs to the next day
+    assert_eq!(
+        playlist_time(&config, "2022-05-10", "02:00:00"),
+        Some(7200.0 + day_offset(&config))
+    );
+
+    assert_eq!(playlist_time(&config, "2022-05-10", "02:00"), None);
+    assert_eq!(playlist_time(&config, "2022-05-10", "aa:00:00"), None);
+
+    // wall time on DST change day, the clock was set back at 03:00
+    config.playlist.timezone = "Europe/Berlin".into();
+    assert_eq!(
+        playlist_time(&config, "2022-10-30", "12:00:00"),
+        Some(46800.0)
+    );
+}
+
+#[test]
+fn rpc_clip_bounds() {
+    let list = vec![
+        media("a.mp4", 0.0, 10.0),
+        media("b.mp4", 10.0, 10.0),
+        media("c.mp4", 20.0, 10.0),
+    ];
+
+    assert_eq!(index_time(&list, 0), Some(0.0));
+    assert_eq!(index_time(&list, 2), Some(20.0));
+    assert_eq!(index_time(&list, 3), None);
+
+    assert_eq!(position_time(&list[1], 0.0), Some(10.0));
+    assert_eq!(position_time(&list[1], 9.5), Some(19.5));
+    assert_eq!(position_time(&list[1], 10.0), None);
+    assert_eq!(position_time(&list[1], -1.0), None);
+
+    // clip begins at its begin time and ends before the next one
+    assert_eq!(clip_at(&list, 10.0).unwrap().source, "b.mp4");
+    assert_eq!(clip_at(&list, 19.99).unwrap().source, "b.mp4");
+    assert_eq!(clip_at(&list, 29.99).unwrap().source, "c.mp4");
+    assert!(clip_at(&list, 30.0).is_none());
+    assert!(clip_at(&list, -1.0).is_none());
+}
+
+#[test]
+fn rpc_move_to() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.playlist.day_start = "00:00:00".into();
+    config.playlist.start_sec = Some(0.0);
+    config.general.stat_file = env::temp_dir()
+        .join("ffp_move_to_status")
+        .to_string_lossy()
+        .to_string();
+    config.out.mode = OutputMode::Stream;
+
+    let proc_control = ProcessControl::new();
+    let playout_stat = PlayoutStatus::new();
+    let mut time_shift = 0.0;
+    let mut date = String::new();
+    let target = get_sec() + 30.0;
+
+    // nothing to move without decoder
+    assert!(move_to(
+        &config,
+        &proc_control,
+        &playout_stat,
+        &mut time_shift,
+        &mut date,
+        "2022-05-10",
+        target,
+    )
+    .is_none());
+
+    let decoder = Command::new("sleep").arg("10").spawn().unwrap();
+    *proc_control.decoder_term.lock().unwrap() = Some(decoder);
+
+    let delta = move_to(
+        &config,
+        &proc_control,
+        &playout_stat,
+        &mut time_shift,
+        &mut date,
+        "2022-05-10",
+        target,
+    )
+    .unwrap();
+
+    assert!((delta - 30.0).abs() < 1.0);
+    assert_eq!(time_shift, delta);
+    assert_eq!(date, "2022-05-10");
+    assert!(playout_stat.list_init.load(Ordering::SeqCst));
+
+    // decoder is stopped, so the playlist takes the clip at the target
+    let mut decoder = proc_control.decoder_term.lock().unwrap();
+    assert!(decoder.as_mut().unwrap().try_wait().unwrap().is_some());
+    drop(decoder);
+
+    // in HLS mode the encoder plays the clip
+    config.out.mode = OutputMode::HLS;
+    playout_stat.list_init.store(false, Ordering::SeqCst);
+
+    let encoder = Command::new("sleep").arg("10").spawn().unwrap();
+    *proc_control.encoder_term.lock().unwrap() = Some(encoder);
+
+    let delta = move_to(
+        &config,
+        &proc_control,
+        &playout_stat,
+        &mut time_shift,
+        &mut date,
+        "2022-05-11",
+        target,
+    )
+    .unwrap();
+
+    assert!((delta - 30.0).abs() < 1.0);
+    assert_eq!(date, "2022-05-11");
+    assert!(playout_stat.list_init.load(Ordering::SeqCst));
+
+    let mut encoder = proc_control.encoder_term.lock().unwrap();
+    assert!(encoder.as_mut().unwrap().try_wait().unwrap().is_some());
+}
+
+#[test]
+fn hold_slate() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.processing.add_logo = false;
+
+    config.storage.slate_clip = "assets/still.jpg".into();
+    let slate = slate_node(&config, &None);
+
+    assert_eq!(slate.source, "assets/still.jpg");
+    assert_eq!(slate.duration, DUMMY_LEN);
+    assert_eq!(
+        slate.cmd.unwrap()[..4],
+        vec_strings!["-loop", "1", "-i", "assets/still.jpg"]
+    );
+
+    // clip gets looped for the chunk length
+    config.storage.slate_clip = "assets/with_audio.mp4".into();
+    let slate = slate_node(&config, &None);
+    let cmd = slate.cmd.unwrap();
+
+    assert_eq!(slate.source, "assets/with_audio.mp4");
+    assert_eq!(slate.duration, DUMMY_LEN);
+    assert_eq!(cmd[0], "-stream_loop");
+    assert!(cmd.ends_with(&vec_strings![
+        "-i",
+        "assets/with_audio.mp4",
+        "-t",
+        DUMMY_LEN
+    ]));
+
+    // without slate, or when it not exists, a dummy clip plays
+    for slate_clip in ["", "assets/missing.mp4", "assets/missing.png"] {
+        config.storage.slate_clip = slate_clip.into();
+        let slate = slate_node(&config, &None);
+
+        assert!(slate.source.starts_with("color=c="));
+        assert_eq!(slate.duration, DUMMY_LEN);
+        assert!(slate.cmd.unwrap().contains(&"lavfi".to_string()));
+    }
+}
+
+#[test]
+fn recover_out_of_sync() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.storage.filler_clip = String::new();
+
+    // behind: clip gets cut at the begin
+    let mut node = media("assets/with_audio.mp4", 100.0, 30.0);
+    assert!(recover_sync(&config, &mut node, -5.0, &None).is_none());
+    assert_eq!(node.seek, 5.0);
+    assert!(node.overlap.is_none());
+
+    // behind more than the clip length: clip gets skipped
+    let mut node = media("assets/with_audio.mp4", 100.0, 3.0);
+    let skipped = recover_sync(&config, &mut node, -5.0, &None).unwrap();
+    assert_eq!(skipped.source, "assets/with_audio.mp4");
+    assert_eq!(skipped.process, Some(false));
+    assert_eq!(node.seek, 0.0);
+
+    // ahead: filler plays before the clip
+    let mut node = media("assets/with_audio.mp4", 100.0, 30.0);
+    let filler = recover_sync(&config, &mut node, 4.0, &None).unwrap();
+    assert_eq!(filler.out, 4.0);
+    assert_eq!(filler.begin, Some(96.0));
+    assert_eq!(filler.filler_queue.len(), 1);
+    assert_eq!(filler.filler_queue[0].source, "assets/with_audio.mp4");
+    assert_eq!(filler.filler_queue[0].seek, 0.0);
+}
@@ -1,9 +1,23 @@
+use std::{
+    env, fs,
+    io::{Read, Write},
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize},
+        Arc, Mutex,
+    },
+    thread,
+};
+
 #[cfg(test)]
 use chrono::prelude::*;
 
 #[cfg(test)]
 use ffplayout_lib::utils::*;
 
+#[cfg(test)]
+use ffplayout_lib::vec_strings;
+
 #[test]
 fn mock_date_time() {
     let time_str = "2022-05-20T06:00:00";
@@ -50,3 +64,869 @@ fn test_delta() {
 
     assert!(delta < 2.0);
 }
+
+#[test]
+fn playlist_hard_start() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.mail.recipient = "".into();
+    config.playlist.day_start = "00:00:00".into();
+    config.playlist.start_sec = Some(0.0);
+    config.playlist.infinit = false;
+    config.logging.log_to_file = false;
+
+    let playlist = read_json(
+        &config,
+        Some("assets/playlist_hard_start.json".into()),
+        Arc::new(AtomicBool::new(true)),
+        false,
+        0.0,
+    );
+
+    let begins: Vec<f64> = playlist.program.iter().map(|m| m.begin.unwrap()).collect();
+
+    assert_eq!(begins, vec![0.0, 100.0, 180.0, 230.0, 240.0]);
+    assert_eq!(playlist.program[1].out, 80.0);
+    assert_eq!(playlist.program[3].source, "");
+    assert_eq!(playlist.program[3].out, 10.0);
+}
+
+#[test]
+fn hard_start_invalid() {
+    let mut media = Media::new(0, "assets/with_audio.mp4", false);
+
+    media.start = Some("ab:cd:ef".into());
+    assert_eq!(media.hard_start(0.0), None);
+
+    media.start = Some("06:00".into());
+    assert_eq!(media.hard_start(0.0), None);
+
+    media.start = Some("05:00:00".into());
+    assert_eq!(media.hard_start(21600.0), Some(104400.0));
+}
+
+#[test]
+fn hard_start_dst() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.playlist.day_start = "00:00:00".into();
+    config.playlist.timezone = "Europe/Berlin".into();
+
+    let mut media = Media::new(0, "assets/with_audio.mp4", false);
+    media.start = Some("06:00:00".into());
+
+    // clocks go back at 03:00, so 06:00 is 7 hours after midnight
+    set_day_times(&mut config, "2022-10-30");
+
+    assert_eq!(config.playlist.length_sec, Some(90000.0));
+    assert_eq!(media.hard_start_sec(&config, "2022-10-30"), Some(25200.0));
+    assert_eq!(media.hard_start_sec(&config, "2022-10-31"), Some(21600.0));
+}
+
+#[test]
+fn playlist_transition_overlap() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.mail.recipient = "".into();
+    config.playlist.day_start = "00:00:00".into();
+    config.playlist.start_sec = Some(0.0);
+    config.playlist.infinit = false;
+    config.logging.log_to_file = false;
+    config.processing.transitions.insert(
+        "promo".into(),
+        Transition {
+            kind: "wipeleft".into(),
+            duration: 1.0,
+        },
+    );
+
+    let playlist = read_json(
+        &config,
+        Some("assets/playlist_transition.json".into()),
+        Arc::new(AtomicBool::new(true)),
+        false,
+        0.0,
+    );
+
+    let begins: Vec<f64> = playlist.program.iter().map(|m| m.begin.unwrap()).collect();
+
+    assert_eq!(begins, vec![0.0, 98.0, 197.0]);
+    assert_eq!(playlist.program[0].out, 98.0);
+    assert_eq!(playlist.program[0].next_overlap, Some(true));
+    assert_eq!(playlist.program[1].out, 99.0);
+    assert_eq!(
+        playlist.program[1].overlap.as_ref().unwrap().kind,
+        "dissolve"
+    );
+
+    let overlap = playlist.program[2].overlap.clone().unwrap();
+
+    assert_eq!(overlap.kind, "wipeleft");
+    assert_eq!(overlap.seek, 99.0);
+    assert_eq!(overlap.duration, 1.0);
+}
+
+#[test]
+fn playlist_expand_block() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.mail.recipient = "".into();
+    config.playlist.path = "assets".into();
+    config.playlist.day_start = "00:00:00".into();
+    config.playlist.start_sec = Some(0.0);
+    config.playlist.infinit = false;
+    config.logging.log_to_file = false;
+
+    let playlist = read_json(
+        &config,
+        Some("assets/playlist_block.json".into()),
+        Arc::new(AtomicBool::new(true)),
+        false,
+        0.0,
+    );
+
+    let begins: Vec<f64> = playlist.program.iter().map(|m| m.begin.unwrap()).collect();
+
+    assert_eq!(begins, vec![0.0, 10.0, 30.0, 50.0]);
+    assert_eq!(playlist.program[2].source, "tests/assets/dual_audio.mp4");
+    assert_eq!(playlist.program[3].index, Some(3));
+}
+
+#[test]
+fn playlist_missing_block() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.playlist.path = "assets".into();
+
+    let playlist: JsonPlaylist = serde_json::from_str(
+        r#"{"program": [
+            {"source": "missing.json", "out": 20.0},
+            {"source": "missing.json"},
+            {"in": 0.0, "out": 10.0, "duration": 10.0, "source": "assets/av_sync.mp4"}
+        ]}"#,
+    )
+    .unwrap();
+
+    let program = expand_blocks(&config, playlist.program);
+
+    assert_eq!(program.len(), 2);
+    assert_eq!(program[0].source, "");
+    assert_eq!(program[0].out, 20.0);
+    assert_eq!(program[1].source, "assets/av_sync.mp4");
+
+    // only block references can leave out the length
+    assert!(serde_json::from_str::<JsonPlaylist>(
+        r#"{"program": [{"in": 0.0, "out": 10.0, "source": "assets/av_sync.mp4"}]}"#
+    )
+    .is_err());
+}
+
+#[test]
+fn import_extended_m3u() {
+    let config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    let program = import::import_program(&config, "assets/import.m3u", None).unwrap();
+
+    assert_eq!(program.len(), 2);
+    assert_eq!(program[0].source, "/media/news.mp4");
+    assert_eq!(program[0].title, "Morning News");
+    assert_eq!(program[0].seek, 10.0);
+    assert_eq!(program[0].out, 60.0);
+    assert_eq!(program[0].duration, 120.5);
+    assert_eq!(program[1].title, "Clip without duration");
+}
+
+#[test]
+fn import_csv_xspf_edl() {
+    let config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+
+    let csv = import::import_program(&config, "assets/import.csv", None).unwrap();
+
+    assert_eq!(csv.len(), 2);
+    assert_eq!(csv[0].title, "Weather; Evening");
+    assert_eq!(csv[0].source, "/media/weather.mp4");
+    assert_eq!(csv[0].out, 90.0);
+    assert_eq!(csv[1].category, "advertisement");
+
+    // with known out the clip is not probed, its length is out - in
+    let csv_path = env::temp_dir().join("ffplayout_import_out.csv");
+    fs::write(&csv_path, "source,in,out\nassets/with_audio.mp4,1,5\n").unwrap();
+
+    let csv = import::import_program(&config, &csv_path.to_string_lossy(), None).unwrap();
+
+    assert_eq!(csv[0].duration, 5.0);
+    assert_eq!(csv[0].out - csv[0].seek, 4.0);
+
+    let xspf = import::import_program(&config, "assets/import.xspf", None).unwrap();
+
+    assert_eq!(xspf[0].source, "/media/my show.mp4");
+    assert_eq!(xspf[0].title, "Show & Tell");
+    assert_eq!(xspf[0].seek, 5.0);
+    assert_eq!(xspf[0].out, 300.0);
+
+    let edl = import::import_program(
+        &config,
+        "assets/import.edl",
+        Some(import::ImportFormat::Edl),
+    )
+    .unwrap();
+
+    assert_eq!(edl.len(), 2);
+    assert_eq!(edl[0].source, "/media/intro.mp4");
+    assert_eq!(edl[0].seek, 10.0);
+    assert!(is_close(edl[0].out, 20.48, 0.001));
+    assert!(is_close(edl[0].duration, 20.48, 0.001));
+    assert_eq!(edl[1].source, "/media/main.mp4");
+    assert_eq!(edl[1].out, 120.0);
+}
+
+#[test]
+fn export_csv_edl() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.playlist.day_start = "00:00:00".into();
+    config.playlist.start_sec = Some(0.0);
+
+    let playlist = json_reader(&"assets/playlist_hard_start.json".into()).unwrap();
+
+    let csv = export::export_playlist(&config, &playlist, export::ExportFormat::Csv);
+    let lines: Vec<&str> = csv.lines().collect();
+
+    assert_eq!(lines[0], "start,source,in,out,duration,category,title");
+    assert_eq!(
+        lines[2],
+        "00:01:40.000,tests/assets/dual_audio.mp4,0,80,100,,"
+    );
+    assert_eq!(
+        lines[3],
+        "00:03:00.000,tests/assets/with_audio.mp4,0,50,50,,"
+    );
+    assert_eq!(lines[4], "00:03:50.000,,0,10,10,,");
+    assert_eq!(
+        lines[5],
+        "00:04:00.000,tests/assets/short_video.mp4,0,10,10,,"
+    );
+
+    let edl = export::export_playlist(&config, &playlist, export::ExportFormat::Edl);
+
+    assert!(edl.starts_with("TITLE: Channel 1 2022-11-01\n"));
+    assert!(edl.contains(
+        "002  AX       AA/V  C        00:00:00:00 00:01:20:00 00:01:40:00 00:03:00:00\n"
+    ));
+    assert!(edl.contains(
+        "004  AX       AA/V  C        00:00:00:00 00:00:10:00 00:03:50:00 00:04:00:00\n"
+    ));
+    assert!(edl.contains("* FROM CLIP NAME: filler\n"));
+    assert!(edl.contains("* SOURCE FILE: tests/assets/short_video.mp4\n"));
+}
+
+#[test]
+fn generate_from_template() {
+    let playlist_path = env::temp_dir().join("ffplayout_template");
+    let _ = fs::remove_dir_all(&playlist_path);
+    fs::create_dir_all(&playlist_path).unwrap();
+
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.mail.recipient = "".into();
+    config.logging.log_to_file = false;
+    config.playlist.path = playlist_path.to_string_lossy().to_string();
+    config.playlist.day_start = "00:00:00".into();
+    config.playlist.start_sec = Some(0.0);
+    config.playlist.length_sec = Some(120.0);
+    config.playlist.template = "assets/template.yml".into();
+    config.storage.filler_clip = "assets/av_sync.mp4".into();
+    config.general.generate = Some(vec![
+        "2022-11-07".to_string(),
+        "-".to_string(),
+        "2022-11-08".to_string(),
+    ]);
+
+    let playlists = generate_playlist(&config, None).unwrap();
+
+    let monday: Vec<(&str, f64)> = playlists[0]
+        .program
+        .iter()
+        .map(|m| (m.source.as_str(), m.out))
+        .collect();
+
+    assert_eq!(
+        monday,
+        vec![
+            ("assets/with_audio.mp4", 30.0),
+            ("assets/av_sync.mp4", 30.0),
+            ("assets/short_video.mp4", 10.0),
+            ("assets/av_sync.mp4", 20.0),
+            ("assets/av_sync.mp4", 30.0),
+        ]
+    );
+    assert_eq!(playlists[0].program[0].category, "news");
+    assert_eq!(playlists[0].program[2].start, Some("00:01:00".to_string()));
+
+    assert_eq!(playlists[1].program[0].source, "assets/ad.mp4");
+    assert_eq!(playlists[1].program.len(), 5);
+
+    // clocks go forward at 02:00, so the day has 23 hours and 23:00 is 22 hours after midnight
+    let template_path = playlist_path.join("template_dst.yml");
+    fs::write(
+        &template_path,
+        "default:\n  - start: \"00:00:00\"\n    duration: \"00:00:30\"\n    source: assets/with_audio.mp4\n  - start: \"23:00:00\"\n    source: assets/ad.mp4\n",
+    )
+    .unwrap();
+
+    config.playlist.timezone = "Europe/Berlin".into();
+    config.playlist.length_sec = Some(86400.0);
+    config.playlist.template = template_path.to_string_lossy().to_string();
+    config.storage.filler_clip = String::new();
+    config.general.generate = Some(vec!["2023-03-26".to_string()]);
+
+    let playlists = generate_playlist(&config, None).unwrap();
+    let program = &playlists[0].program;
+    let slot = program
+        .iter()
+        .position(|m| m.start.as_deref() == Some("23:00:00"))
+        .unwrap();
+    let length = |list: &[Media]| list.iter().map(|c| c.out - c.seek).sum::<f64>();
+
+    assert_eq!(program[slot].source, "assets/ad.mp4");
+    assert_eq!(length(&program[..slot]), 79200.0);
+    assert!((length(program) - 82800.0).abs() < 0.01);
+}
+
+#[test]
+fn remote_playlist_from_cache() {
+    let cache_path = env::temp_dir().join("ffplayout_cache_test");
+    let _ = fs::remove_dir_all(&cache_path);
+    fs::create_dir_all(&cache_path).unwrap();
+    fs::copy(
+        "assets/playlist_hard_start.json",
+        cache_path.join("127.0.0.1_1_playlist.json"),
+    )
+    .unwrap();
+
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.playlist.cache_path = cache_path.to_string_lossy().to_string();
+
+    // server is not reachable, so the last known good copy is used
+    let (playlist, _) = fetch_remote_playlist(&config, "http://127.0.0.1:1/playlist.json").unwrap();
+
+    assert_eq!(playlist.date, "2022-11-01");
+    assert!(fetch_remote_playlist(&config, "http://127.0.0.1:1/missing.json").is_none());
+}
+
+#[test]
+fn remote_playlist_invalid_marker() {
+    let cache_path = env::temp_dir().join("ffplayout_cache_invalid");
+    let _ = fs::remove_dir_all(&cache_path);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/playlist.json", listener.local_addr().unwrap());
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut stream = stream;
+            let mut buffer = [0; 1024];
+            let _ = stream.read(&mut buffer);
+            let _ = stream.write_all(
+                b"HTTP/1.1 200 OK\r\nETag: \"v2\"\r\nContent-Length: 7\r\nConnection: close\r\n\r\ninvalid",
+            );
+        }
+    });
+
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.playlist.cache_path = cache_path.to_string_lossy().to_string();
+
+    let cache_name: String = url
+        .trim_start_matches("http://")
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect();
+    fs::create_dir_all(&cache_path).unwrap();
+    fs::copy(
+        "assets/playlist_hard_start.json",
+        cache_path.join(cache_name),
+    )
+    .unwrap();
+
+    // the invalid version is not used, but its marker, so the playout not reloads again
+    let (playlist, modified) = fetch_remote_playlist(&config, &url).unwrap();
+
+    assert_eq!(playlist.date, "2022-11-01");
+    assert_eq!(modified, Some("\"v2\"".to_string()));
+}
+
+#[test]
+fn dst_day_length() {
+    let tz = parse_time_zone("Europe/Berlin").unwrap();
+    let date = |d| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+
+    assert!(parse_time_zone("Mars/Olympus").is_err());
+    assert_eq!(day_length(tz, date("2022-03-27")), 82800.0);
+    assert_eq!(day_length(tz, date("2022-10-30")), 90000.0);
+    assert_eq!(day_length(tz, date("2022-11-01")), 86400.0);
+
+    // broadcast day from 06:00 to 06:00 wall time
+    assert_eq!(
+        broadcast_day(tz, date("2022-03-26"), 21600.0),
+        (21600.0, 82800.0)
+    );
+    assert_eq!(
+        broadcast_day(tz, date("2022-03-27"), 21600.0),
+        (18000.0, 86400.0)
+    );
+    assert_eq!(
+        broadcast_day(tz, date("2022-10-29"), 21600.0),
+        (21600.0, 90000.0)
+    );
+    assert_eq!(
+        broadcast_day(tz, date("2022-10-30"), 21600.0),
+        (25200.0, 86400.0)
+    );
+}
+
+#[test]
+fn dst_sec_since_midnight() {
+    let tz = parse_time_zone("Europe/Berlin").unwrap();
+
+    // wall clock shows 06:00, but only 5 hours are gone since midnight
+    mock_time::set_mock_time("2022-03-27T06:00:00+02:00");
+    let sec = sec_since_midnight(tz, time_now().with_timezone(&Utc));
+
+    assert!(is_close(sec, 18000.0, 1.0));
+
+    // last second from a 25 hour day
+    mock_time::set_mock_time("2022-10-30T23:59:59+01:00");
+    let sec = sec_since_midnight(tz, time_now().with_timezone(&Utc));
+
+    assert!(is_close(sec, 89999.0, 1.0));
+}
+
+#[test]
+fn frame_time_without_drift() {
+    let rate = FrameRate::from_fps(29.97);
+
+    assert_eq!(rate, FrameRate::new(30000, 1001));
+    assert_eq!("30000/1001".parse::<FrameRate>(), Ok(rate));
+    assert!("0/1".parse::<FrameRate>().is_err());
+
+    // 10.01 seconds are exactly 300 frames in NTSC
+    let clip = FrameTime::from_sec(10.01, rate);
+    let mut total = FrameTime::zero(rate);
+
+    for _ in 0..8640 {
+        total += clip;
+    }
+
+    assert_eq!(clip.frames(), 300);
+    assert_eq!(total.frames(), 2_592_000);
+    assert_eq!(total.sec(), 86486.4);
+
+    // cuts are rounded to the nearest frame
+    let pal = FrameRate::from_fps(25.0);
+
+    assert_eq!(FrameTime::from_sec(1.03, pal).to_string(), "1.04");
+    assert_eq!(FrameTime::new(91537, pal).timecode(), "01:01:01:12");
+    assert_eq!(
+        FrameTime::new(50, pal),
+        FrameTime::new(100, FrameRate::new(50, 1))
+    );
+}
+
+#[test]
+fn clock_sources() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.general.clock = ClockMode::Offset;
+    config.general.clock_offset = -3600.0;
+
+    let offset = Clock::from_config(&config).unwrap();
+    let behind = Utc::now() - offset.now();
+
+    assert_eq!(offset, Clock::Offset(-3600.0));
+    assert!((behind.num_milliseconds() - 3_600_000).abs() < 1000);
+
+    config.general.clock = ClockMode::Accelerated;
+    config.general.clock_speed = 0.0;
+
+    assert!(Clock::from_config(&config).is_err());
+
+    // one hour per minute
+    let start = Utc.with_ymd_and_hms(2022, 11, 1, 5, 59, 0).unwrap();
+    let accelerated = Clock::accelerated(start, 60.0);
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    let elapsed = accelerated.now() - start;
+
+    assert_eq!(accelerated.speed(), 60.0);
+    assert!(elapsed.num_milliseconds() >= 6000 && elapsed.num_milliseconds() < 60_000);
+}
+
+#[test]
+fn scte35_splice_insert() {
+    let cue_out = SpliceInsert::cue_out(1234, 30.0);
+    let section = cue_out.section();
+
+    assert_eq!(
+        cue_out.hex(),
+        "0xFC302000000000000000FFF00F05000004D27FFFFE002932E00001000000004DD727A4"
+    );
+    assert_eq!(crc32_mpeg2(&section), 0);
+
+    let cue_in = SpliceInsert::cue_in(1234);
+    let packet = cue_in.ts_packet(500, 3);
+
+    assert_eq!(packet[..4], [0x47, 0x41, 0xF4, 0x13]);
+    assert_eq!(packet[5..5 + cue_in.section().len()], cue_in.section()[..]);
+    assert_eq!(crc32_mpeg2(&cue_in.section()), 0);
+}
+
+#[test]
+fn live_item_input() {
+    let item: Media = serde_json::from_str(
+        r#"{"in": 0, "out": 1800, "duration": 1800, "type": "live", "source": "srt://127.0.0.1:9000"}"#,
+    )
+    .unwrap();
+
+    assert!(item.is_live());
+    assert_eq!(
+        live_input(&item, 10.0),
+        vec_strings![
+            "-rw_timeout",
+            "10000000",
+            "-i",
+            "srt://127.0.0.1:9000",
+            "-t",
+            "1800"
+        ]
+    );
+
+    let json = serde_json::to_string(&item).unwrap();
+    assert!(json.contains(r#""type":"live""#));
+
+    let clip = Media::new(0, "tests/assets/with_audio.mp4", false);
+    assert!(!clip.is_live());
+    assert!(!serde_json::to_string(&clip).unwrap().contains(r#""type""#));
+}
+
+#[test]
+fn filler_best_fit() {
+    let clips: Vec<Media> = [
+        ("a.mp4", 30.0),
+        ("b.mp4", 20.0),
+        ("c.mp4", 15.0),
+        ("d.mp4", 10.0),
+    ]
+    .iter()
+    .map(|(source, duration)| {
+        let mut clip = Media::new(0, source, false);
+        clip.duration = *duration;
+        clip.out = *duration;
+        clip
+    })
+    .collect();
+    let length = |list: &[Media]| list.iter().map(|c| c.out - c.seek).sum::<f64>();
+
+    let recent = std::collections::VecDeque::from(vec!["a.mp4".to_string()]);
+    let fitted = best_fit(&clips, &recent, 45.0);
+
+    assert_eq!(length(&fitted), 45.0);
+    assert!(fitted
+        .iter()
+        .all(|c| c.source != "a.mp4" && c.out == c.duration));
+
+    let cut = best_fit(&clips, &recent, 7.0);
+
+    assert_eq!(cut.len(), 1);
+    assert_eq!(cut[0].out, 7.0);
+
+    let long = best_fit(&clips, &std::collections::VecDeque::new(), 200.0);
+
+    assert!((length(&long) - 200.0).abs() < 0.05);
+}
+
+#[test]
+fn filler_pool_rescan() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    let folder = env::temp_dir().join("ffplayout_filler_pool");
+    let _ = fs::remove_dir_all(&folder);
+    fs::create_dir_all(&folder).unwrap();
+    fs::copy("assets/with_audio.mp4", folder.join("a.mp4")).unwrap();
+    config.storage.filler_clip = folder.to_string_lossy().to_string();
+
+    let first = fill_gap(&config, 30.0);
+
+    assert_eq!(first.len(), 1);
+    assert!(first[0].source.ends_with("a.mp4"));
+
+    // new clip in folder, it comes before the recently played one
+    thread::sleep(std::time::Duration::from_millis(50));
+    fs::copy("assets/with_audio.mp4", folder.join("b.mp4")).unwrap();
+
+    let second = fill_gap(&config, 30.0);
+
+    assert_eq!(second.len(), 1);
+    assert!(second[0].source.ends_with("b.mp4"));
+
+    fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+fn supervisor_restarts() {
+    let proc_control = ProcessControl::new();
+    let supervisor = Supervisor::default();
+
+    assert_eq!(supervisor.max_failures, 5);
+    assert_eq!(supervisor.delay, 2.0);
+
+    proc_control.supervise(2, 0.0);
+
+    assert!(!proc_control.restart(Encoder));
+    assert!(proc_control.failed(Encoder, "encoder has stopped"));
+    // same failure, reported from stderr and from the player
+    assert!(proc_control.failed(Encoder, "encoder has stopped"));
+    assert!(proc_control.restart(Encoder));
+    assert!(!proc_control.restart(Encoder));
+
+    // decoder restarts without delay, the encoder is waiting for data
+    proc_control.supervise(2, 30.0);
+    let start = std::time::Instant::now();
+
+    assert!(proc_control.failed(Decoder, "Invalid argument"));
+    assert!(proc_control.restart(Decoder));
+    assert!(start.elapsed().as_secs_f64() < 1.0);
+    assert!(!proc_control.failed(Decoder, "Invalid argument"));
+
+    let supervisor = proc_control.supervisor.lock().unwrap();
+
+    assert_eq!(supervisor.failures, 3);
+    assert_eq!(supervisor.restarts, 2);
+    assert_eq!(
+        supervisor.last_failure.as_deref(),
+        Some("Decoder: Invalid argument")
+    );
+}
+
+#[test]
+fn rotation_rules() {
+    let storage = env::temp_dir().join("ffplayout_rotation");
+    let _ = fs::remove_dir_all(&storage);
+
+    for file in [
+        "jingles/j1.mp4",
+        "idents/i1.mp4",
+        "music/m1.mp4",
+        "music/m2.mp4",
+        "music/m3.mp4",
+        "o1.mp4",
+        "o2.mp4",
+    ] {
+        let path = storage.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.storage.path = storage.to_string_lossy().to_string();
+    config.storage.shuffle = false;
+    config.storage.rotation = vec![
+        RotationRule {
+            folder: "jingles".into(),
+            every: 2,
+            ..Default::default()
+        },
+        RotationRule {
+            folder: "idents".into(),
+            hourly: true,
+            ..Default::default()
+        },
+        RotationRule {
+            folder: "music".into(),
+            percent: 60.0,
+            ..Default::default()
+        },
+    ];
+
+    let clips: Vec<Media> = fs::read_dir(&storage)
+        .unwrap()
+        .flat_map(|e| e.ok())
+        .flat_map(|e| match e.path().is_dir() {
+            true => fs::read_dir(e.path())
+                .unwrap()
+                .flat_map(|e| e.ok())
+                .collect(),
+            false => vec![e],
+        })
+        .map(|e| Media::new(0, &e.path().to_string_lossy(), false))
+        .collect();
+
+    let mut rotation = Rotation::new(&config, &clips).unwrap();
+    let round: Vec<String> = rotation
+        .round()
+        .iter()
+        .map(|c| c.source.replace(&config.storage.path, ""))
+        .collect();
+
+    assert_eq!(
+        round,
+        vec![
+            "/music/m1.mp4",
+            "/o1.mp4",
+            "/jingles/j1.mp4",
+            "/music/m2.mp4",
+            "/o2.mp4",
+            "/jingles/j1.mp4",
+            "/music/m3.mp4",
+        ]
+    );
+
+    assert!(rotation.hourly(3500.0).is_none());
+    assert!(rotation.hourly(3550.0).is_none());
+    assert!(rotation
+        .hourly(3610.0)
+        .map_or(false, |c| c.source.ends_with("idents/i1.mp4")));
+}
+
+#[test]
+fn shuffle_no_repeat() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.general.stat_file = String::new();
+    config.storage.no_repeat_count = 2;
+
+    let mut history = PlayHistory::new(&config);
+
+    for source in ["a.mp4", "b.mp4", "c.mp4"] {
+        let mut node = Media::new(0, source, false);
+        node.out = 10.0;
+        history.add(&node);
+    }
+
+    assert_eq!(history.entries.len(), 2);
+
+    let order = |sources: &[&str], count: usize, time: f64| -> Vec<String> {
+        let clips = sources.iter().map(|s| Media::new(0, s, false)).collect();
+
+        no_repeat_order(clips, &history, count, time)
+            .into_iter()
+            .map(|c| c.source)
+            .collect()
+    };
+
+    assert_eq!(
+        order(&["c.mp4", "b.mp4", "a.mp4", "d.mp4"], 2, 0.0),
+        vec!["a.mp4", "b.mp4", "c.mp4", "d.mp4"]
+    );
+    assert_eq!(
+        order(&["c.mp4", "b.mp4", "d.mp4", "e.mp4"], 0, 15.0),
+        vec!["d.mp4", "b.mp4", "c.mp4", "e.mp4"]
+    );
+    assert_eq!(order(&["c.mp4", "b.mp4"], 5, 0.0), vec!["b.mp4", "c.mp4"]);
+}
+
+#[test]
+fn folder_state_restore() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    let state = folder::FolderState {
+        index: 2,
+        order: vec_strings!["b.mp4", "c.mp4", "a.mp4"],
+    };
+    let clips: Vec<Media> = ["a.mp4", "b.mp4", "d.mp4"]
+        .iter()
+        .map(|s| Media::new(0, s, false))
+        .collect();
+
+    let sources = |(list, index): (Vec<Media>, usize)| {
+        let list: Vec<String> = list.into_iter().map(|c| c.source).collect();
+        (list, index)
+    };
+
+    config.storage.shuffle = true;
+    assert_eq!(
+        state.restore(&config, &clips).map(sources),
+        Some((vec_strings!["b.mp4", "a.mp4", "d.mp4"], 1))
+    );
+
+    config.storage.shuffle = false;
+    assert_eq!(
+        state.restore(&config, &clips).map(sources),
+        Some((vec_strings!["a.mp4", "b.mp4", "d.mp4"], 0))
+    );
+
+    assert!(state.restore(&config, &clips[2..]).is_none());
+}
+
+#[test]
+fn folder_state_save() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    let stat_file = env::temp_dir().join("ffplayout_folder_state");
+    let order_file = stat_file.with_extension("folder");
+    let index_file = stat_file.with_extension("folder_index");
+    let _ = fs::remove_file(&order_file);
+
+    config.general.stat_file = stat_file.to_string_lossy().to_string();
+    config.storage.path = "assets".into();
+    config.storage.shuffle = false;
+
+    let mut source = folder::FolderSource::new(
+        &config,
+        None,
+        Arc::new(Mutex::new(vec![])),
+        Arc::new(AtomicUsize::new(0)),
+    );
+    let first = source.next().unwrap();
+    let order: Vec<String> =
+        serde_json::from_str(&fs::read_to_string(&order_file).unwrap()).unwrap();
+
+    assert_eq!(order[0], first.source);
+    assert_eq!(fs::read_to_string(&index_file).unwrap(), "0");
+
+    // order is the same, only the position gets written
+    fs::remove_file(&order_file).unwrap();
+    source.next().unwrap();
+
+    assert!(!order_file.exists());
+    assert_eq!(fs::read_to_string(&index_file).unwrap(), "1");
+
+    fs::remove_file(&index_file).unwrap();
+}
+
+#[test]
+fn program_guide() {
+    let mut config = PlayoutConfig::new(Some("../assets/ffplayout.yml".to_string()));
+    config.playlist.day_start = "00:00:00".into();
+    config.playlist.start_sec = Some(0.0);
+    config.playlist.timezone = "Europe/Berlin".into();
+    config.text.regex = String::new();
+
+    let mut playlist = json_reader(&"assets/playlist_hard_start.json".into()).unwrap();
+    playlist.program[2].title = "News & Weather".into();
+    playlist.program[2].season = Some(1);
+    playlist.program[2].episode = Some(4);
+
+    // like in playout: second clip is cut, filler before the last one is not listed
+    let program = epg::program_items(&config, playlist);
+    let starts: Vec<&str> = program.iter().map(|p| p.start.as_str()).collect();
+
+    assert_eq!(
+        starts,
+        vec![
+            "2022-11-01 00:00:00.000+01:00",
+            "2022-11-01 00:01:40.000+01:00",
+            "2022-11-01 00:03:00.000+01:00",
+            "2022-11-01 00:04:00.000+01:00",
+        ]
+    );
+    assert_eq!(program[1].out, 80.0);
+
+    let xml = epg::xmltv(1, "Channel <1>", &program);
+
+    assert!(xml.contains("<display-name>Channel &lt;1&gt;</display-name>"));
+    assert!(xml.contains(
+        "<programme start=\"20221101000300 +0100\" stop=\"20221101000350 +0100\" channel=\"1\">"
+    ));
+    assert!(xml.contains("<title>News &amp; Weather</title>"));
+    assert!(xml.contains("<episode-num system=\"xmltv_ns\">0.3.</episode-num>"));
+
+    let events = epg::eit(&program);
+
+    assert_eq!(events.len(), 4);
+    assert_eq!(events[1].event_id, 2);
+    assert_eq!(events[1].start_time, "2022-10-31 23:01:40");
+    assert_eq!(events[1].duration, "00:01:20");
+    assert_eq!(events[1].running_status, 1);
+    assert_eq!(events[2].short_event.event_name, "News & Weather");
+}